@@ -1,14 +1,25 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::util::DEFAULT_JSON_NAME;
-
 
 #[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Clone)]
 pub struct Bookmark {
+    // Empty until the bookmark is added to a collection
+    #[serde(default)]
+    id: String,
     command: String,
     annotation: String,
     tags: Vec<String>,
     collection: String,
+    // Maintained by the store, missing for bookmarks saved by older versions
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_used_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    use_count: u64,
 }
 
 impl Bookmark {
@@ -22,15 +33,24 @@ impl Bookmark {
     /// * `collection` - A string slice that holds the collection
     pub fn new(command: &str, annotation: &str, tags: &Vec<String>, collection: &str) -> Bookmark {
         Bookmark {
+            id: String::new(),
             command: command.to_string(),
             annotation: annotation.to_string(),
             tags: tags.to_owned(),
             collection: collection.to_string(),
+            created_at: None,
+            updated_at: None,
+            last_used_at: None,
+            use_count: 0,
         }
     }
 
-    pub fn default() -> Bookmark {
-        Bookmark::new("", "", &Vec::new(), DEFAULT_JSON_NAME)
+    pub fn get_id(&self) -> &String {
+        &self.id
+    }
+
+    pub fn set_id(&mut self, id: &str) {
+        self.id = id.to_string();
     }
 
     pub fn get_command(&self) -> &String {
@@ -41,6 +61,10 @@ impl Bookmark {
         &self.annotation
     }
 
+    pub fn get_tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
     pub fn get_tags_as_string(&self, delimiter: &str) -> String {
         (self.tags).join(delimiter)
     }
@@ -49,6 +73,92 @@ impl Bookmark {
         &self.collection
     }
 
+    pub fn set_collection(&mut self, collection: &str) {
+        self.collection = collection.to_string();
+    }
+
+    pub fn get_created_at(&self) -> Option<&DateTime<Utc>> {
+        self.created_at.as_ref()
+    }
+
+    pub fn get_updated_at(&self) -> Option<&DateTime<Utc>> {
+        self.updated_at.as_ref()
+    }
+
+    pub fn get_last_used_at(&self) -> Option<&DateTime<Utc>> {
+        self.last_used_at.as_ref()
+    }
+
+    pub fn get_use_count(&self) -> u64 {
+        self.use_count
+    }
+
+    /// Set the timestamps and use count, for stores that save them apart
+    /// from the bookmark
+    pub fn set_usage(
+        &mut self,
+        created_at: Option<DateTime<Utc>>,
+        updated_at: Option<DateTime<Utc>>,
+        last_used_at: Option<DateTime<Utc>>,
+        use_count: u64
+    ) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+        self.last_used_at = last_used_at;
+        self.use_count = use_count;
+    }
+
+    /// Stamp a bookmark being added, unless it already has a creation time
+    pub fn mark_created(&mut self) {
+        if self.created_at.is_none() {
+            let now = Utc::now();
+            self.created_at = Some(now);
+            self.updated_at = Some(now);
+        }
+    }
+
+    /// Stamp a bookmark replacing old, keeping the creation time and usage
+    /// of old
+    pub fn mark_updated(&mut self, old: &Bookmark) {
+        self.created_at = old.created_at;
+        self.last_used_at = old.last_used_at;
+        self.use_count = old.use_count;
+        self.updated_at = Some(Utc::now());
+    }
+
+    /// Record that the command was used
+    pub fn mark_used(&mut self) {
+        self.last_used_at = Some(Utc::now());
+        self.use_count += 1;
+    }
+
+    /// Returns true if other has the same command, annotation and tags
+    pub fn has_same_content(&self, other: &Bookmark) -> bool {
+        self.command == other.command && self.annotation == other.annotation && self.tags == other.tags
+    }
+
+    /// Returns the command without quotes and with whitespace collapsed, so
+    /// commands that only differ in quoting or spacing compare equal
+    pub fn get_normalized_command(&self) -> String {
+        let unquoted: String = self.command.chars().filter(|c| *c != '\'' && *c != '"').collect();
+        unquoted.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    /// Returns true if other runs the same command, ignoring quoting and
+    /// spacing differences
+    pub fn is_duplicate_of(&self, other: &Bookmark) -> bool {
+        self.get_normalized_command() == other.get_normalized_command()
+    }
+
+    /// Returns true if keyword is found in the command, annotation,
+    /// collection or any of the tags
+    pub fn contains(&self, keyword: &str) -> bool {
+        self.command.contains(keyword)
+            || self.annotation.contains(keyword)
+            || self.collection.contains(keyword)
+            || self.tags.iter().any(|tag| tag.contains(keyword))
+    }
+
     pub fn to_vec(&self) -> Vec<String> {
         vec![
             self.command.to_string(),
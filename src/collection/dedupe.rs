@@ -0,0 +1,144 @@
+use super::bookmark::Bookmark;
+use super::store::{SearchMode, SearchResult, Store};
+use crate::error::{MemoireError, Result};
+
+
+// Separates the annotations of merged duplicates
+static ANNOTATION_SEPARATOR: &str = "; ";
+
+
+/// Returns the bookmarks of any collection running the same command as
+/// bookmark, ignoring quoting and spacing differences
+pub fn find_duplicates(store: &dyn Store, bookmark: &Bookmark) -> Result<Vec<SearchResult>> {
+    Ok(
+        store.search(&[""], SearchMode::Union)?.into_iter().filter(
+            |result| result.get_bookmark().get_id() != bookmark.get_id()
+                && result.get_bookmark().is_duplicate_of(bookmark)
+        ).collect()
+    )
+}
+
+
+/// Returns the groups of bookmarks running the same command, across
+/// collections, in the order their first bookmark is found
+pub fn find_duplicate_groups(store: &dyn Store) -> Result<Vec<Vec<SearchResult>>> {
+    let mut groups: Vec<Vec<SearchResult>> = Vec::new();
+    for result in store.search(&[""], SearchMode::Union)? {
+        match groups.iter_mut().find(|group| group[0].get_bookmark().is_duplicate_of(result.get_bookmark())) {
+            Some(group) => group.push(result),
+            None => groups.push(vec![result])
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    Ok(groups)
+}
+
+
+/// Returns the distinct non-empty annotations of group, in order
+pub fn get_distinct_annotations(group: &[SearchResult]) -> Vec<&str> {
+    let mut annotations: Vec<&str> = Vec::new();
+    for result in group {
+        let annotation = result.get_bookmark().get_annotation().trim();
+        if !annotation.is_empty() && !annotations.contains(&annotation) {
+            annotations.push(annotation);
+        }
+    }
+    annotations
+}
+
+
+/// Returns the distinct annotations of group joined together, in order
+pub fn merge_annotations(group: &[SearchResult]) -> String {
+    get_distinct_annotations(group).join(ANNOTATION_SEPARATOR)
+}
+
+
+/// Merge a group of duplicates into the bookmark at keep, which gets the tags
+/// of every bookmark and annotation, the others are moved to the trash
+///
+/// Returns the merged bookmark. Fails with MemoireError::ReadOnly before
+/// changing anything if a bookmark is in a read-only collection.
+///
+/// # Arguments
+///
+/// * `store` - The store holding the duplicates
+/// * `group` - The duplicates, as returned by find_duplicate_groups
+/// * `keep` - The index in group of the bookmark to keep
+/// * `annotation` - The annotation of the merged bookmark
+pub fn merge_duplicates(store: &dyn Store, group: &[SearchResult], keep: usize, annotation: &str) -> Result<Bookmark> {
+    for result in group {
+        let collection = result.get_bookmark().get_collection();
+        if store.is_read_only(collection)? {
+            return Err(MemoireError::ReadOnly(collection.to_owned()));
+        }
+    }
+    let kept = group[keep].get_bookmark();
+    let mut tags: Vec<String> = Vec::new();
+    // Tags of the kept bookmark first
+    let bookmarks = std::iter::once(kept).chain(group.iter().map(|result| result.get_bookmark()));
+    for tag in bookmarks.flat_map(|bookmark| bookmark.get_tags()) {
+        if !tags.contains(tag) {
+            tags.push(tag.to_owned());
+        }
+    }
+    let mut merged = Bookmark::new(kept.get_command(), annotation, &tags, kept.get_collection());
+    merged.set_id(kept.get_id());
+    store.update(kept.get_id(), &merged)?;
+    for (index, result) in group.iter().enumerate() {
+        if index != keep {
+            store.delete(result.get_bookmark().get_id())?;
+        }
+    }
+    Ok(merged)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::{json::JsonStore, metadata::CollectionMetadata};
+
+
+    fn add(store: &dyn Store, command: &str, annotation: &str, tags: &[&str], collection: &str) {
+        let tags: Vec<String> = tags.iter().map(|tag| tag.to_string()).collect();
+        store.add(&Bookmark::new(command, annotation, &tags, collection), None).unwrap();
+    }
+
+
+    #[test]
+    fn merge_keeps_tags_and_trashes_others() {
+        let dir = tempdir().unwrap();
+        let store = JsonStore::open(dir.path().to_str().unwrap(), "default").unwrap();
+        add(&store, "ls  -la", "list", &["fs"], "default");
+        add(&store, "ls -la", "list all", &["shell"], "tools");
+        let groups = find_duplicate_groups(&store).unwrap();
+        assert_eq!(groups.len(), 1);
+        let merged = merge_duplicates(&store, &groups[0], 0, &merge_annotations(&groups[0])).unwrap();
+        assert_eq!(merged.get_annotation(), "list; list all");
+        assert_eq!(merged.get_tags(), &vec!["fs".to_owned(), "shell".to_owned()]);
+        assert!(store.list("tools").unwrap().is_empty());
+        assert_eq!(store.list_trash().unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn merge_with_read_only_member_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let store = JsonStore::open(dir.path().to_str().unwrap(), "default").unwrap();
+        add(&store, "ls -la", "list", &["fs"], "default");
+        add(&store, "ls -la", "list all", &["shell"], "tools");
+        let mut metadata = CollectionMetadata::default();
+        metadata.set_read_only(true);
+        store.set_metadata("tools", &metadata).unwrap();
+        let groups = find_duplicate_groups(&store).unwrap();
+        assert!(matches!(
+            merge_duplicates(&store, &groups[0], 0, "merged"),
+            Err(MemoireError::ReadOnly(collection)) if collection == "tools"
+        ));
+        assert_eq!(store.list("default").unwrap()[0].get_bookmark().get_annotation(), "list");
+        assert_eq!(store.list("tools").unwrap().len(), 1);
+        assert!(store.list_trash().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,384 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{read_dir, read_to_string, remove_file, rename},
+    path::Path
+};
+
+use rusqlite::Connection;
+
+use super::bookmark::Bookmark;
+use super::journal::JournalEntry;
+use super::json::{JOURNAL_NAME, METADATA_NAME, PENDING_MOVE_NAME, TRASH_NAME, UNNAMED_JSON_NAME, write_bookmarks};
+use super::metadata::CollectionMetadata;
+use super::migration::{parse_collection, salvage};
+use super::sqlite::DB_FILE_NAME;
+use super::store::{TrashedBookmark, generate_id, validate_collection_name};
+use super::util::{LOCK_FILE_NAME, lock_collection_dir, remove_json};
+use crate::error::{MemoireError, Result};
+
+
+/// A problem found in the collection directory
+pub struct Issue {
+    path: String,
+    description: String,
+    // What --fix does about it, None if it has to be repaired by hand
+    repair: Option<String>,
+}
+
+
+/// Collection file being checked, along with its repaired bookmarks
+struct CollectionCheck {
+    path: String,
+    name: String,
+    bookmarks: Vec<Bookmark>,
+    // Whether the file has to be rewritten to repair it
+    changed: bool,
+}
+
+
+impl Issue {
+    fn new(path: &str, description: &str, repair: Option<&str>) -> Issue {
+        Issue {
+            path: path.to_owned(),
+            description: description.to_owned(),
+            repair: repair.map(|repair| repair.to_owned())
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn get_repair(&self) -> Option<&str> {
+        self.repair.as_deref()
+    }
+}
+
+
+/// Check the collection directory for malformed collection files, bookmarks
+/// of another collection, duplicate bookmarks and ids, bookmarks saved
+/// without a collection, unreadable trash, journal or metadata and stray files
+///
+/// Returns every issue found, the ones with a repair are repaired if fix is
+/// true. Repaired files keep their previous version as a backup.
+///
+/// # Arguments
+///
+/// * `dir_path` - A string slice that holds the collection directory
+/// * `default_collection` - The collection bookmarks without one are moved to
+/// * `fix` - Whether to repair the issues
+pub fn diagnose(dir_path: &str, default_collection: &str, fix: bool) -> Result<Vec<Issue>> {
+    let mut issues: Vec<Issue> = Vec::new();
+    if !Path::new(dir_path).is_dir() {
+        return Ok(issues);
+    }
+    let _lock = if fix {
+        Some(lock_collection_dir(dir_path)?)
+    } else {
+        None
+    };
+
+    let mut entries: Vec<_> = read_dir(dir_path).map_err(
+        |err| MemoireError::Io(dir_path.to_owned(), err)
+    )?.filter_map(|entry| entry.ok()).collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    let mut collections: Vec<CollectionCheck> = Vec::new();
+    let mut unnamed: Option<CollectionCheck> = None;
+    for entry in entries {
+        let path = entry.path().to_string_lossy().into_owned();
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(_) => {
+                issues.push(Issue::new(&path, "file name is not valid unicode", None));
+                continue;
+            }
+        };
+        if name == LOCK_FILE_NAME || name == PENDING_MOVE_NAME || name.starts_with(DB_FILE_NAME) || is_backup(&name) {
+            continue;
+        }
+        if name.ends_with(".tmp") {
+            issues.push(Issue::new(&path, "leftover of an interrupted write", Some("remove it")));
+            if fix {
+                remove_file(&path).map_err(|err| MemoireError::Io(path.to_owned(), err))?;
+            }
+        } else if name == UNNAMED_JSON_NAME {
+            if let Some(mut check) = check_collection(&path, default_collection, &mut issues)? {
+                issues.push(Issue::new(
+                    &path,
+                    "bookmarks saved without a collection",
+                    Some(&format!("move them to the end of collection {}", default_collection))
+                ));
+                check.changed = true;
+                unnamed = Some(check);
+            }
+        } else if name == TRASH_NAME {
+            check_sidecar(&path, fix, &mut issues, |content| {
+                serde_json::from_str::<Vec<TrashedBookmark>>(content).map(|_| ())
+            })?;
+        } else if name == JOURNAL_NAME {
+            check_sidecar(&path, fix, &mut issues, |content| {
+                content.lines().try_for_each(|line| serde_json::from_str::<JournalEntry>(line).map(|_| ()))
+            })?;
+        } else if name == METADATA_NAME {
+            check_sidecar(&path, fix, &mut issues, |content| {
+                serde_json::from_str::<BTreeMap<String, CollectionMetadata>>(content).map(|_| ())
+            })?;
+        } else if name.starts_with('.') {
+            // Other hidden files, like the .git of a shared checkout or the
+            // files --fix set aside, are not ours to check
+            continue;
+        } else if !name.ends_with(".json") || !entry.path().is_file() {
+            issues.push(Issue::new(&path, "not a collection file", None));
+        } else {
+            let collection = name.trim_end_matches(".json");
+            if let Err(err) = validate_collection_name(collection) {
+                issues.push(Issue::new(&path, &err.to_string(), None));
+            }
+            if let Some(check) = check_collection(&path, collection, &mut issues)? {
+                collections.push(check);
+            }
+        }
+    }
+
+    let unnamed_path = unnamed.as_ref().map(|check| check.path.to_owned());
+    if let Some(unnamed) = unnamed {
+        let default_path = format!("{}/{}.json", dir_path, default_collection);
+        let index = match collections.iter().position(|check| check.name == default_collection) {
+            Some(index) => index,
+            None => {
+                collections.push(CollectionCheck {
+                    path: default_path,
+                    name: default_collection.to_owned(),
+                    bookmarks: Vec::new(),
+                    changed: true
+                });
+                collections.len() - 1
+            }
+        };
+        let default = &mut collections[index];
+        default.bookmarks.extend(unnamed.bookmarks.into_iter().map(|mut bookmark| {
+            bookmark.set_collection(default_collection);
+            bookmark
+        }));
+        default.changed = true;
+    }
+
+    check_ids(&mut collections, &mut issues);
+    if fix {
+        for check in collections.iter().filter(|check| check.changed) {
+            write_bookmarks(&check.path, &check.bookmarks)?;
+        }
+        // Only removed once its bookmarks are safe in the default collection
+        if let Some(unnamed_path) = unnamed_path {
+            remove_json(&unnamed_path)?;
+        }
+    }
+
+    let db_path = format!("{}/{}", dir_path, DB_FILE_NAME);
+    if Path::new(&db_path).exists() {
+        check_database(&db_path, &mut issues)?;
+    }
+    Ok(issues)
+}
+
+
+/// Returns true for the backups kept when a collection file is replaced
+fn is_backup(name: &str) -> bool {
+    name.ends_with(".bak") && name.contains(".json.")
+}
+
+
+/// Check a file memoire keeps next to the collections, setting it aside if
+/// parse fails on its content and fix is true
+///
+/// Memoire starts over without the file once it is set aside, the broken file
+/// is kept to recover its entries by hand.
+///
+/// # Arguments
+///
+/// * `path` - A string slice that holds the path of the file
+/// * `fix` - Whether to set the file aside if it is broken
+/// * `issues` - The issues found so far
+/// * `parse` - Parses the content of the file
+fn check_sidecar<F>(path: &str, fix: bool, issues: &mut Vec<Issue>, parse: F) -> Result<()>
+where
+    F: Fn(&str) -> serde_json::Result<()>
+{
+    let content = read_to_string(path).map_err(|err| MemoireError::Io(path.to_owned(), err))?;
+    if let Err(err) = parse(&content) {
+        let mut broken_path = format!("{}.broken", path);
+        let mut count = 1;
+        while Path::new(&broken_path).exists() {
+            broken_path = format!("{}.broken.{}", path, count);
+            count += 1;
+        }
+        issues.push(Issue::new(path, &err.to_string(), Some(&format!("move it aside to {}", broken_path))));
+        if fix {
+            rename(path, &broken_path).map_err(|err| MemoireError::Io(path.to_owned(), err))?;
+        }
+    }
+    Ok(())
+}
+
+
+/// Check the collection file at json_path, reporting its issues
+///
+/// Returns the bookmarks that can be kept, None if the file has to be
+/// repaired by hand
+fn check_collection(json_path: &str, collection: &str, issues: &mut Vec<Issue>) -> Result<Option<CollectionCheck>> {
+    let content = read_to_string(json_path).map_err(
+        |err| MemoireError::Io(json_path.to_owned(), err)
+    )?;
+    let mut check = CollectionCheck {
+        path: json_path.to_owned(),
+        name: collection.to_owned(),
+        bookmarks: Vec::new(),
+        changed: false
+    };
+    if content.trim().is_empty() {
+        issues.push(Issue::new(json_path, "empty file", Some("write an empty collection")));
+        check.changed = true;
+        return Ok(Some(check));
+    }
+
+    match parse_collection(json_path, &content, collection) {
+        Ok((bookmarks, _)) => check.bookmarks = bookmarks,
+        Err(MemoireError::InvalidCollection(_, diagnostics)) => match salvage(json_path, &content, collection) {
+            Ok((bookmarks, salvage_diagnostics)) => {
+                for diagnostic in salvage_diagnostics {
+                    issues.push(Issue::new(
+                        json_path,
+                        &diagnostic.to_string(),
+                        Some("rewrite the collection file")
+                    ));
+                }
+                check.bookmarks = bookmarks;
+                check.changed = true;
+            },
+            Err(_) => {
+                for diagnostic in diagnostics {
+                    issues.push(Issue::new(json_path, &diagnostic.to_string(), None));
+                }
+                return Ok(None);
+            }
+        },
+        Err(err) => {
+            issues.push(Issue::new(json_path, &err.to_string(), None));
+            return Ok(None);
+        }
+    }
+
+    // Keep the first of bookmarks with the same content
+    let mut kept: Vec<Bookmark> = Vec::new();
+    for bookmark in check.bookmarks.drain(..) {
+        match kept.iter().find(|other| other.has_same_content(&bookmark)) {
+            Some(other) => {
+                issues.push(Issue::new(
+                    json_path,
+                    &format!("bookmark {} duplicates bookmark {}", bookmark.get_id(), other.get_id()),
+                    Some("remove it")
+                ));
+                check.changed = true;
+            },
+            None => kept.push(bookmark)
+        }
+    }
+    check.bookmarks = kept;
+    Ok(Some(check))
+}
+
+
+/// Make sure every bookmark has an id no other bookmark uses
+fn check_ids(collections: &mut [CollectionCheck], issues: &mut Vec<Issue>) {
+    let mut all_ids: HashSet<String> = collections.iter()
+        .flat_map(|check| check.bookmarks.iter().map(|bookmark| bookmark.get_id().to_owned()))
+        .collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    for check in collections.iter_mut() {
+        for bookmark in check.bookmarks.iter_mut() {
+            let description = if bookmark.get_id().is_empty() {
+                format!("bookmark \"{}\" has no id", bookmark.get_command())
+            } else if seen.contains(bookmark.get_id()) {
+                format!("id {} is used by more than one bookmark", bookmark.get_id())
+            } else {
+                seen.insert(bookmark.get_id().to_owned());
+                continue;
+            };
+            issues.push(Issue::new(&check.path, &description, Some("assign a new id")));
+            let id = generate_id(&all_ids);
+            all_ids.insert(id.to_owned());
+            seen.insert(id.to_owned());
+            bookmark.set_id(&id);
+            check.changed = true;
+        }
+    }
+}
+
+
+/// Run the sqlite integrity check on the database at db_path
+fn check_database(db_path: &str, issues: &mut Vec<Issue>) -> Result<()> {
+    let conn = Connection::open(db_path)?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+    for row in rows {
+        let row = row?;
+        if row != "ok" {
+            issues.push(Issue::new(db_path, &row, None));
+        }
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::json::JsonStore;
+    use crate::collection::store::Store;
+
+
+    #[test]
+    fn fix_sets_broken_trash_aside() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+        write(dir.path().join(".trash.json"), "[{").unwrap();
+        write(dir.path().join(".git"), "").unwrap();
+
+        let issues = diagnose(dir_path, "default", false).unwrap();
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].get_path().ends_with(".trash.json"));
+        assert!(issues[0].get_repair().unwrap().ends_with(".trash.json.broken"));
+
+        diagnose(dir_path, "default", true).unwrap();
+        assert!(!dir.path().join(".trash.json").exists());
+        assert_eq!(read_to_string(dir.path().join(".trash.json.broken")).unwrap(), "[{");
+        assert!(diagnose(dir_path, "default", false).unwrap().is_empty());
+        let store = JsonStore::open(dir_path, "default").unwrap();
+        assert!(store.get_trash_error().is_none());
+        assert!(store.list_trash().unwrap().is_empty());
+    }
+
+
+    #[test]
+    fn broken_journal_and_metadata_are_reported() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+        write(dir.path().join(".journal.jsonl"), "not json\n").unwrap();
+        write(dir.path().join(".metadata.json"), r#"{"work": 3}"#).unwrap();
+        write(dir.path().join(".metadata.json.broken"), "").unwrap();
+
+        let issues = diagnose(dir_path, "default", true).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert!(dir.path().join(".journal.jsonl.broken").exists());
+        assert!(dir.path().join(".metadata.json.broken.1").exists());
+    }
+}
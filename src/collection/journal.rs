@@ -0,0 +1,232 @@
+use std::{
+    env,
+    fmt::{Display, Formatter}
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::bookmark::Bookmark;
+use super::store::{SearchResult, Store};
+use crate::error::{MemoireError, Result};
+
+
+/// Change made to a bookmark, as recorded in the journal
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Add,
+    Edit,
+    Delete,
+    Restore,
+    Move
+}
+
+
+impl Display for Operation {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Operation::Add => "add",
+            Operation::Edit => "edit",
+            Operation::Delete => "delete",
+            Operation::Restore => "restore",
+            Operation::Move => "move"
+        })
+    }
+}
+
+
+/// Entry of the append-only journal of changes made to bookmarks
+///
+/// before is the bookmark and its index before the change, None for a
+/// bookmark that was added or restored. after is the bookmark and its index
+/// after the change, None for a bookmark that was deleted.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    // Sequence number, assigned by the store
+    #[serde(skip)]
+    id: u64,
+    operation: Operation,
+    collection: String,
+    before: Option<Bookmark>,
+    before_index: Option<usize>,
+    after: Option<Bookmark>,
+    after_index: Option<usize>,
+    // User who made the change, from $USER
+    user: String,
+    time: DateTime<Utc>,
+}
+
+
+impl JournalEntry {
+    /// Returns the entry of a change made now by the current user
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - The change made
+    /// * `before` - The bookmark before the change, None if it did not exist
+    /// * `after` - The bookmark after the change, None if it was deleted
+    pub fn new(operation: Operation, before: Option<&SearchResult>, after: Option<&SearchResult>) -> JournalEntry {
+        let collection = after.or(before)
+            .map(|result| result.get_bookmark().get_collection().to_owned())
+            .unwrap_or_default();
+        JournalEntry {
+            id: 0,
+            operation,
+            collection,
+            before: before.map(|result| result.get_bookmark().to_owned()),
+            before_index: before.map(|result| result.get_index()),
+            after: after.map(|result| result.get_bookmark().to_owned()),
+            after_index: after.map(|result| result.get_index()),
+            user: env::var("USER").unwrap_or_default(),
+            time: Utc::now()
+        }
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn set_id(&mut self, id: u64) {
+        self.id = id;
+    }
+
+    pub fn get_operation(&self) -> Operation {
+        self.operation
+    }
+
+    pub fn get_collection(&self) -> &str {
+        &self.collection
+    }
+
+    pub fn get_before(&self) -> Option<&Bookmark> {
+        self.before.as_ref()
+    }
+
+    pub fn get_before_index(&self) -> Option<usize> {
+        self.before_index
+    }
+
+    pub fn get_after(&self) -> Option<&Bookmark> {
+        self.after.as_ref()
+    }
+
+    pub fn get_after_index(&self) -> Option<usize> {
+        self.after_index
+    }
+
+    pub fn get_user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn get_time(&self) -> &DateTime<Utc> {
+        &self.time
+    }
+
+    /// Returns the bookmark the entry is about, after the change if it still
+    /// exists
+    pub fn get_bookmark(&self) -> &Bookmark {
+        self.after.as_ref().or(self.before.as_ref()).expect("journal entry without bookmark")
+    }
+}
+
+
+/// Undo the change recorded in entry, the undo is journaled as a change of
+/// its own
+///
+/// Fails if the bookmark changed in a way that makes the undo impossible,
+/// like reverting an edit of a bookmark that was deleted since. Returns a
+/// description of what was done.
+pub fn revert(store: &dyn Store, entry: &JournalEntry) -> Result<String> {
+    let id = entry.get_bookmark().get_id().to_owned();
+    let exists = store.find(&id)?.is_some();
+    let missing = || MemoireError::InvalidArgument(
+        format!("Bookmark {} no longer exists, entry {} can not be reverted", id, entry.get_id())
+    );
+    match (entry.get_operation(), &entry.before) {
+        (Operation::Add, _) | (Operation::Restore, _) => {
+            if !exists {
+                return Err(missing());
+            }
+            store.delete(&id)?;
+            Ok(format!("Moved bookmark {} to the trash", id))
+        },
+        (Operation::Delete, Some(before)) => {
+            if exists {
+                return Err(MemoireError::InvalidArgument(
+                    format!("Bookmark {} already exists, entry {} can not be reverted", id, entry.get_id())
+                ));
+            }
+            if store.list_trash()?.iter().any(|trashed| trashed.get_bookmark().get_id() == &id) {
+                store.restore(&id)?;
+            } else {
+                store.add(before, entry.get_before_index())?;
+            }
+            Ok(format!("Put bookmark {} back in collection {}", id, before.get_collection()))
+        },
+        (Operation::Edit, Some(before)) => {
+            if !exists {
+                return Err(missing());
+            }
+            store.update(&id, before)?;
+            // Moving it back to its collection appended it, put it back in place
+            if let (Some(after), Some(index)) = (entry.get_after(), entry.get_before_index()) {
+                if after.get_collection() != before.get_collection() {
+                    store.move_bookmark(&id, index)?;
+                }
+            }
+            Ok(format!("Reverted bookmark {} to its previous version", id))
+        },
+        (Operation::Move, _) => {
+            if !exists {
+                return Err(missing());
+            }
+            let to = store.move_bookmark(&id, entry.get_before_index().unwrap_or_default())?;
+            Ok(format!("Moved bookmark {} back to index {}", id, to))
+        },
+        (_, None) => Err(MemoireError::InvalidArgument(
+            format!("Entry {} does not record the bookmark before the change", entry.get_id())
+        ))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::json::JsonStore;
+    use crate::collection::sqlite::SqliteStore;
+
+
+    fn list_commands(store: &dyn Store, collection: &str) -> Vec<String> {
+        store.list(collection).unwrap().iter().map(
+            |result| result.get_bookmark().get_command().to_owned()
+        ).collect()
+    }
+
+
+    #[test]
+    fn revert_moves_bookmark_back_to_its_index() {
+        let json_dir = tempdir().unwrap();
+        let sqlite_dir = tempdir().unwrap();
+        let stores: Vec<Box<dyn Store>> = vec![
+            Box::new(JsonStore::open(json_dir.path().to_str().unwrap(), "default").unwrap()),
+            Box::new(SqliteStore::open(sqlite_dir.path().to_str().unwrap(), "default").unwrap())
+        ];
+        for store in stores {
+            let store = store.as_ref();
+            let ids: Vec<String> = ["a", "b", "c"].iter().map(
+                |command| store.add(&Bookmark::new(command, "", &Vec::new(), "x"), None).unwrap()
+            ).collect();
+            store.update(&ids[0], &Bookmark::new("a", "", &Vec::new(), "y")).unwrap();
+            assert_eq!(list_commands(store, "x"), vec!["b", "c"]);
+
+            let entry = store.log().unwrap().pop().unwrap();
+            revert(store, &entry).unwrap();
+            assert_eq!(list_commands(store, "x"), vec!["a", "b", "c"]);
+            assert!(list_commands(store, "y").is_empty());
+        }
+    }
+}
@@ -0,0 +1,848 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{OpenOptions, metadata, read_dir, read_to_string, remove_file},
+    io::Write,
+    path::Path
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::bookmark::Bookmark;
+use super::journal::{JournalEntry, Operation};
+use super::metadata::CollectionMetadata;
+use super::migration::{CollectionFile, parse_collection, parse_foreign_collection};
+use super::store::{
+    BrokenCollection, SearchMode, SearchResult, Store, TrashedBookmark, generate_id, validate_collection_name
+};
+use super::util::{
+    create_collection_dir, lock_collection_dir, remove_json, write_file, write_to_json
+};
+use crate::error::{MemoireError, Result};
+
+
+// Records a move between two collection files until both are written
+pub static PENDING_MOVE_NAME: &str = ".pending_move.json";
+// File older versions saved bookmarks added without a collection to
+pub static UNNAMED_JSON_NAME: &str = ".json";
+// Deleted bookmarks, oldest first
+pub static TRASH_NAME: &str = ".trash.json";
+// Changes made to bookmarks, one json entry per line, oldest first
+pub static JOURNAL_NAME: &str = ".journal.jsonl";
+// Metadata of the collections that have any, by collection name
+pub static METADATA_NAME: &str = ".metadata.json";
+
+
+/// Store keeping every collection as a versioned json list of bookmarks in
+/// `<dir_path>/<collection>.json`
+pub struct JsonStore {
+    dir_path: String,
+    broken: Vec<BrokenCollection>,
+    // Why the trash could not be read on open, it is left untouched
+    trash_error: Option<MemoireError>
+}
+
+
+impl Store for JsonStore {
+    fn collections(&self) -> Result<Vec<String>> {
+        Ok(
+            self.get_json_paths()?.iter().map(|json_path| get_collection_name(json_path)).collect()
+        )
+    }
+
+    fn get_broken_collections(&self) -> &[BrokenCollection] {
+        &self.broken
+    }
+
+    fn get_trash_error(&self) -> Option<&MemoireError> {
+        self.trash_error.as_ref()
+    }
+
+    fn create_collection(&self, name: &str) -> Result<()> {
+        validate_collection_name(name)?;
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let json_path = self.get_json_path(name);
+        if Path::new(&json_path).exists() {
+            return Err(collection_exists(name));
+        }
+        write_bookmarks(&json_path, &[])
+    }
+
+    fn rename_collection(&self, from: &str, to: &str) -> Result<usize> {
+        validate_collection_name(to)?;
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let from_path = self.get_existing_json_path(from)?;
+        self.ensure_writable(from)?;
+        let to_path = self.get_json_path(to);
+        if Path::new(&to_path).exists() {
+            return Err(collection_exists(to));
+        }
+        let mut bookmarks = read_bookmarks(&from_path)?;
+        for bookmark in bookmarks.iter_mut() {
+            bookmark.set_collection(to);
+        }
+        self.transfer(
+            PendingMove::new(&bookmarks, from, to, true),
+            None,
+            &bookmarks
+        )?;
+        let mut metadata = self.read_metadata()?;
+        if let Some(collection_metadata) = metadata.remove(from) {
+            metadata.insert(to.to_owned(), collection_metadata);
+            self.write_metadata(&metadata)?;
+        }
+        Ok(bookmarks.len())
+    }
+
+    fn merge_collections(&self, from: &str, into: &str) -> Result<usize> {
+        if from == into {
+            return Err(MemoireError::InvalidArgument(
+                format!("Can not merge collection {} into itself", from)
+            ));
+        }
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let from_path = self.get_existing_json_path(from)?;
+        let into_path = self.get_existing_json_path(into)?;
+        self.ensure_writable(from)?;
+        self.ensure_writable(into)?;
+        let mut bookmarks = read_bookmarks(&from_path)?;
+        for bookmark in bookmarks.iter_mut() {
+            bookmark.set_collection(into);
+        }
+        let pending_move = PendingMove::new(&bookmarks, from, into, true);
+        let count = bookmarks.len();
+        let mut into_bookmarks = read_bookmarks(&into_path)?;
+        into_bookmarks.append(&mut bookmarks);
+        self.transfer(pending_move, None, &into_bookmarks)?;
+        self.remove_metadata(from)?;
+        Ok(count)
+    }
+
+    fn delete_collection(&self, name: &str) -> Result<usize> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let json_path = self.get_existing_json_path(name)?;
+        self.ensure_writable(name)?;
+        let bookmarks = read_bookmarks(&json_path)?;
+        let count = bookmarks.len();
+        let mut trash = self.read_trash()?;
+        trash.extend(
+            bookmarks.iter().enumerate().map(|(index, bookmark)| TrashedBookmark::new(index, bookmark.to_owned()))
+        );
+        // Trashed first so a failure never loses the bookmarks
+        self.write_trash(&trash)?;
+        remove_json(&json_path)?;
+        self.remove_metadata(name)?;
+        for (index, bookmark) in bookmarks.into_iter().enumerate() {
+            self.record(JournalEntry::new(Operation::Delete, Some(&SearchResult::new(index, bookmark)), None))?;
+        }
+        Ok(count)
+    }
+
+    fn get_metadata(&self, collection: &str) -> Result<CollectionMetadata> {
+        Ok(self.read_metadata()?.remove(collection).unwrap_or_default())
+    }
+
+    fn set_metadata(&self, collection: &str, collection_metadata: &CollectionMetadata) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        self.get_existing_json_path(collection)?;
+        let mut metadata = self.read_metadata()?;
+        metadata.insert(collection.to_owned(), collection_metadata.to_owned());
+        self.write_metadata(&metadata)
+    }
+
+    fn is_read_only(&self, collection: &str) -> Result<bool> {
+        if self.get_metadata(collection)?.get_read_only() {
+            return Ok(true);
+        }
+        // Files are replaced rather than written in place, so the permissions
+        // of the file itself have to be checked
+        let json_path = self.get_json_path(collection);
+        match metadata(&json_path) {
+            Ok(file_metadata) => Ok(file_metadata.permissions().readonly()),
+            Err(_) => Ok(false)
+        }
+    }
+
+    fn list(&self, collection: &str) -> Result<Vec<SearchResult>> {
+        Ok(
+            read_bookmarks(&self.get_json_path(collection))?
+                .into_iter()
+                .enumerate()
+                .map(|(index, bookmark)| SearchResult::new(index, bookmark))
+                .collect()
+        )
+    }
+
+    fn search(&self, keywords: &[&str], mode: SearchMode) -> Result<Vec<SearchResult>> {
+        let mut results: Vec<SearchResult> = Vec::new();
+        for json_path in self.get_json_paths()? {
+            for (index, bookmark) in read_bookmarks(&json_path)?.into_iter().enumerate() {
+                let found = match mode {
+                    SearchMode::Union => keywords.iter().any(|keyword| bookmark.contains(keyword)),
+                    SearchMode::Intersection => keywords.iter().all(|keyword| bookmark.contains(keyword))
+                };
+                if found {
+                    results.push(SearchResult::new(index, bookmark));
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    fn find(&self, id: &str) -> Result<Option<SearchResult>> {
+        for json_path in self.get_json_paths()? {
+            let bookmarks = read_bookmarks(&json_path)?;
+            if let Some(index) = bookmarks.iter().position(|bookmark| bookmark.get_id() == id) {
+                return Ok(Some(SearchResult::new(index, bookmarks[index].to_owned())));
+            }
+        }
+        Ok(None)
+    }
+
+    fn add(&self, bookmark: &Bookmark, index: Option<usize>) -> Result<String> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        self.ensure_writable(bookmark.get_collection())?;
+        let mut bookmark = bookmark.to_owned();
+        if bookmark.get_id().is_empty() {
+            bookmark.set_id(&generate_id(&self.get_all_ids()?));
+        }
+        bookmark.mark_created();
+        let id = bookmark.get_id().to_owned();
+        let json_path = self.get_new_or_existing_json_path(bookmark.get_collection())?;
+        let mut bookmarks = read_bookmarks(&json_path)?;
+        let index = match index {
+            Some(index) if index <= bookmarks.len() => index,
+            _ => bookmarks.len()
+        };
+        bookmarks.insert(index, bookmark.to_owned());
+        write_bookmarks(&json_path, &bookmarks)?;
+        self.record(JournalEntry::new(Operation::Add, None, Some(&SearchResult::new(index, bookmark))))?;
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, bookmark: &Bookmark) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let (json_path, mut bookmarks, index) = self.locate(id)?;
+        self.ensure_writable(bookmarks[index].get_collection())?;
+        self.ensure_writable(bookmark.get_collection())?;
+        let mut bookmark = bookmark.to_owned();
+        bookmark.set_id(id);
+        bookmark.mark_updated(&bookmarks[index]);
+        let old = SearchResult::new(index, bookmarks[index].to_owned());
+        if bookmarks[index].get_collection() == bookmark.get_collection() {
+            bookmarks[index] = bookmark.to_owned();
+            write_bookmarks(&json_path, &bookmarks)?;
+            let new = SearchResult::new(index, bookmark);
+            return self.record(JournalEntry::new(Operation::Edit, Some(&old), Some(&new)));
+        }
+        let new_json_path = self.get_new_or_existing_json_path(bookmark.get_collection())?;
+        let pending_move = PendingMove::new(
+            &[bookmark.to_owned()],
+            bookmarks[index].get_collection(),
+            bookmark.get_collection(),
+            false
+        );
+        let mut new_bookmarks = read_bookmarks(&new_json_path)?;
+        let new = SearchResult::new(new_bookmarks.len(), bookmark.to_owned());
+        new_bookmarks.push(bookmark);
+        bookmarks.remove(index);
+        self.transfer(pending_move, Some(&bookmarks), &new_bookmarks)?;
+        self.record(JournalEntry::new(Operation::Edit, Some(&old), Some(&new)))
+    }
+
+    fn mark_used(&self, id: &str) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let (json_path, mut bookmarks, index) = self.locate(id)?;
+        if self.is_read_only(bookmarks[index].get_collection())? {
+            return Ok(());
+        }
+        bookmarks[index].mark_used();
+        write_bookmarks(&json_path, &bookmarks)
+    }
+
+    fn delete(&self, id: &str) -> Result<SearchResult> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let (json_path, mut bookmarks, index) = self.locate(id)?;
+        self.ensure_writable(bookmarks[index].get_collection())?;
+        let bookmark = bookmarks.remove(index);
+        let mut trash = self.read_trash()?;
+        trash.push(TrashedBookmark::new(index, bookmark.to_owned()));
+        // Trashed first so a failure never loses the bookmark
+        self.write_trash(&trash)?;
+        write_bookmarks(&json_path, &bookmarks)?;
+        let result = SearchResult::new(index, bookmark);
+        self.record(JournalEntry::new(Operation::Delete, Some(&result), None))?;
+        Ok(result)
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashedBookmark>> {
+        let mut trash = self.read_trash()?;
+        trash.reverse();
+        Ok(trash)
+    }
+
+    fn restore(&self, id: &str) -> Result<SearchResult> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let mut trash = self.read_trash()?;
+        let position = trash.iter().rposition(|trashed| trashed.get_bookmark().get_id() == id).ok_or_else(
+            || MemoireError::InvalidArgument(format!("No bookmark with id {} in the trash", id))
+        )?;
+        let trashed = trash.remove(position);
+        let bookmark = trashed.get_bookmark().to_owned();
+        self.ensure_writable(bookmark.get_collection())?;
+        let json_path = self.get_new_or_existing_json_path(bookmark.get_collection())?;
+        let mut bookmarks = read_bookmarks(&json_path)?;
+        // A restore interrupted before the trash was written already put it back
+        let index = match bookmarks.iter().position(|other| other.get_id() == id) {
+            Some(index) => index,
+            None => {
+                let index = trashed.get_index().min(bookmarks.len());
+                bookmarks.insert(index, bookmark.to_owned());
+                write_bookmarks(&json_path, &bookmarks)?;
+                index
+            }
+        };
+        self.write_trash(&trash)?;
+        let result = SearchResult::new(index, bookmark);
+        self.record(JournalEntry::new(Operation::Restore, None, Some(&result)))?;
+        Ok(result)
+    }
+
+    fn empty_trash(&self) -> Result<usize> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let count = self.read_trash()?.len();
+        self.write_trash(&[])?;
+        Ok(count)
+    }
+
+    fn move_bookmark(&self, id: &str, to: usize) -> Result<usize> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let (json_path, mut bookmarks, from) = self.locate(id)?;
+        self.ensure_writable(bookmarks[from].get_collection())?;
+        let bookmark = bookmarks.remove(from);
+        let to = to.min(bookmarks.len());
+        bookmarks.insert(to, bookmark.to_owned());
+        write_bookmarks(&json_path, &bookmarks)?;
+        self.record(JournalEntry::new(
+            Operation::Move,
+            Some(&SearchResult::new(from, bookmark.to_owned())),
+            Some(&SearchResult::new(to, bookmark))
+        ))?;
+        Ok(to)
+    }
+
+    fn log(&self) -> Result<Vec<JournalEntry>> {
+        let journal_path = self.get_journal_path();
+        if !Path::new(&journal_path).exists() {
+            return Ok(Vec::new());
+        }
+        let content = read_to_string(&journal_path).map_err(
+            |err| MemoireError::Io(journal_path.to_owned(), err)
+        )?;
+        content.lines().enumerate().map(|(index, line)| {
+            let mut entry: JournalEntry = serde_json::from_str(line).map_err(
+                |err| MemoireError::Json(journal_path.to_owned(), err)
+            )?;
+            // Entries are numbered by their line
+            entry.set_id(index as u64 + 1);
+            Ok(entry)
+        }).collect()
+    }
+}
+
+
+impl JsonStore {
+    /// Open the json store in dir_path, creating the directory if needed
+    ///
+    /// Collection files that can not be loaded are skipped and reported by
+    /// get_broken_collections. Files in an older format are upgraded and
+    /// bookmarks saved before ids were introduced are assigned one. Bookmarks
+    /// saved without a collection are moved to default_collection.
+    pub fn open(dir_path: &str, default_collection: &str) -> Result<JsonStore> {
+        create_collection_dir(dir_path)?;
+        let mut store = JsonStore {
+            dir_path: dir_path.to_owned(),
+            broken: Vec::new(),
+            trash_error: None
+        };
+        store.finish_pending_move()?;
+        store.broken = store.find_broken_collections()?;
+        store.trash_error = store.read_trash().err();
+        store.upgrade_jsons()?;
+        store.adopt_unnamed_collection(default_collection)?;
+        store.backfill_ids()?;
+        Ok(store)
+    }
+
+    fn get_json_path(&self, collection: &str) -> String {
+        format!("{}/{}.json", &self.dir_path, collection)
+    }
+
+    /// Returns the path of collection, fails if it does not exist
+    fn get_existing_json_path(&self, collection: &str) -> Result<String> {
+        let json_path = self.get_json_path(collection);
+        if !Path::new(&json_path).exists() {
+            return Err(MemoireError::InvalidArgument(format!("No collection named {}", collection)));
+        }
+        Ok(json_path)
+    }
+
+    /// Returns the path of collection, validating its name if it does not
+    /// exist yet
+    fn get_new_or_existing_json_path(&self, collection: &str) -> Result<String> {
+        let json_path = self.get_json_path(collection);
+        if !Path::new(&json_path).exists() {
+            validate_collection_name(collection)?;
+        }
+        Ok(json_path)
+    }
+
+    fn get_pending_move_path(&self) -> String {
+        format!("{}/{}", &self.dir_path, PENDING_MOVE_NAME)
+    }
+
+    fn get_journal_path(&self) -> String {
+        format!("{}/{}", &self.dir_path, JOURNAL_NAME)
+    }
+
+    /// Append entry to the journal, after the change it records was written
+    fn record(&self, entry: JournalEntry) -> Result<()> {
+        let journal_path = self.get_journal_path();
+        let line = serde_json::to_string(&entry).map_err(
+            |err| MemoireError::Json(journal_path.to_owned(), err)
+        )?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&journal_path)
+            .map_err(|err| MemoireError::Io(journal_path.to_owned(), err))?;
+        writeln!(file, "{}", line).and_then(|_| file.sync_all()).map_err(
+            |err| MemoireError::Io(journal_path.to_owned(), err)
+        )
+    }
+
+    fn get_trash_path(&self) -> String {
+        format!("{}/{}", &self.dir_path, TRASH_NAME)
+    }
+
+    /// Returns the trashed bookmarks, oldest first
+    fn read_trash(&self) -> Result<Vec<TrashedBookmark>> {
+        let trash_path = self.get_trash_path();
+        if !Path::new(&trash_path).exists() {
+            return Ok(Vec::new());
+        }
+        let content = read_to_string(&trash_path).map_err(
+            |err| MemoireError::Io(trash_path.to_owned(), err)
+        )?;
+        serde_json::from_str(&content).map_err(|err| MemoireError::BrokenTrash(trash_path, err))
+    }
+
+    fn write_trash(&self, trash: &[TrashedBookmark]) -> Result<()> {
+        let trash_path = self.get_trash_path();
+        let content = serde_json::to_string_pretty(trash).map_err(
+            |err| MemoireError::Json(trash_path.to_owned(), err)
+        )?;
+        write_file(&trash_path, &content)
+    }
+
+    fn get_metadata_path(&self) -> String {
+        format!("{}/{}", &self.dir_path, METADATA_NAME)
+    }
+
+    fn read_metadata(&self) -> Result<BTreeMap<String, CollectionMetadata>> {
+        let metadata_path = self.get_metadata_path();
+        if !Path::new(&metadata_path).exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = read_to_string(&metadata_path).map_err(
+            |err| MemoireError::Io(metadata_path.to_owned(), err)
+        )?;
+        serde_json::from_str(&content).map_err(|err| MemoireError::Json(metadata_path, err))
+    }
+
+    fn write_metadata(&self, metadata: &BTreeMap<String, CollectionMetadata>) -> Result<()> {
+        let metadata_path = self.get_metadata_path();
+        let content = serde_json::to_string_pretty(metadata).map_err(
+            |err| MemoireError::Json(metadata_path.to_owned(), err)
+        )?;
+        write_file(&metadata_path, &content)
+    }
+
+    /// Drop the metadata of a collection that was removed
+    fn remove_metadata(&self, collection: &str) -> Result<()> {
+        let mut metadata = self.read_metadata()?;
+        if metadata.remove(collection).is_some() {
+            self.write_metadata(&metadata)?;
+        }
+        Ok(())
+    }
+
+    /// Fails with MemoireError::ReadOnly if collection is read-only
+    fn ensure_writable(&self, collection: &str) -> Result<()> {
+        if self.is_read_only(collection)? {
+            return Err(MemoireError::ReadOnly(collection.to_owned()));
+        }
+        Ok(())
+    }
+
+    /// Write the collections on both ends of pending_move, the destination
+    /// first so a failure never loses the bookmarks
+    ///
+    /// # Arguments
+    ///
+    /// * `pending_move` - The move being done, recorded until it is complete
+    /// * `from_bookmarks` - The new bookmarks of the source collection, None if it is removed
+    /// * `to_bookmarks` - The new bookmarks of the destination collection
+    fn transfer(
+        &self,
+        pending_move: PendingMove,
+        from_bookmarks: Option<&[Bookmark]>,
+        to_bookmarks: &[Bookmark]
+    ) -> Result<()> {
+        let pending_move_path = self.get_pending_move_path();
+        let content = serde_json::to_string(&pending_move).map_err(
+            |err| MemoireError::Json(pending_move_path.to_owned(), err)
+        )?;
+        write_file(&pending_move_path, &content)?;
+        write_bookmarks(&self.get_json_path(&pending_move.to), to_bookmarks)?;
+        let from_path = self.get_json_path(&pending_move.from);
+        match from_bookmarks {
+            Some(bookmarks) => write_bookmarks(&from_path, bookmarks)?,
+            None => remove_json(&from_path)?
+        }
+        remove_file(&pending_move_path).map_err(
+            |err| MemoireError::Io(pending_move_path.to_owned(), err)
+        )
+    }
+
+    /// Complete a move interrupted after the destination was written, or
+    /// drop it if the destination was never written
+    fn finish_pending_move(&self) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let pending_move_path = self.get_pending_move_path();
+        if !Path::new(&pending_move_path).exists() {
+            return Ok(());
+        }
+        let content = read_to_string(&pending_move_path).map_err(
+            |err| MemoireError::Io(pending_move_path.to_owned(), err)
+        )?;
+        let pending_move: PendingMove = serde_json::from_str(&content).map_err(
+            |err| MemoireError::Json(pending_move_path.to_owned(), err)
+        )?;
+        let to_ids: HashSet<String> = read_bookmarks(&self.get_json_path(&pending_move.to))?
+            .into_iter()
+            .map(|bookmark| bookmark.get_id().to_owned())
+            .collect();
+        if pending_move.ids.iter().all(|id| to_ids.contains(id)) {
+            let from_path = self.get_json_path(&pending_move.from);
+            if pending_move.remove_source {
+                if Path::new(&from_path).exists() {
+                    remove_json(&from_path)?;
+                }
+            } else {
+                let mut bookmarks = read_bookmarks(&from_path)?;
+                bookmarks.retain(|bookmark| !pending_move.ids.contains(bookmark.get_id()));
+                write_bookmarks(&from_path, &bookmarks)?;
+            }
+        }
+        remove_file(&pending_move_path).map_err(
+            |err| MemoireError::Io(pending_move_path.to_owned(), err)
+        )
+    }
+
+    /// Consume the store, returning the collections it could not load
+    pub fn into_broken_collections(self) -> Vec<BrokenCollection> {
+        self.broken
+    }
+
+    /// Returns the collections whose file can not be loaded
+    fn find_broken_collections(&self) -> Result<Vec<BrokenCollection>> {
+        let mut broken: Vec<BrokenCollection> = Vec::new();
+        for json_path in self.get_json_paths()? {
+            if let Err(err) = parse_json(&json_path) {
+                broken.push(BrokenCollection::new(&get_collection_name(&json_path), err));
+            }
+        }
+        Ok(broken)
+    }
+
+    /// Rewrite the files in an older format with the current one
+    fn upgrade_jsons(&self) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        for json_path in self.get_json_paths()? {
+            let (bookmarks, outdated) = parse_json(&json_path)?;
+            if outdated {
+                write_bookmarks(&json_path, &bookmarks)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the bookmarks added without a collection, saved in `.json` by
+    /// older versions, to the end of default_collection
+    ///
+    /// A `.json` that can not be loaded is left in place and reported as a
+    /// broken collection.
+    fn adopt_unnamed_collection(&mut self, default_collection: &str) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let unnamed_path = self.get_json_path("");
+        if !Path::new(&unnamed_path).exists() {
+            return Ok(());
+        }
+        let content = read_to_string(&unnamed_path).map_err(
+            |err| MemoireError::Io(unnamed_path.to_owned(), err)
+        )?;
+        // Its bookmarks have an empty collection, not the ".json" file stem
+        let unnamed = match parse_foreign_collection(&unnamed_path, &content, default_collection) {
+            Ok((unnamed, _)) => unnamed,
+            Err(err) => {
+                self.broken.push(BrokenCollection::new(UNNAMED_JSON_NAME, err));
+                return Ok(());
+            }
+        };
+        let default_path = self.get_new_or_existing_json_path(default_collection)?;
+        let mut bookmarks = read_bookmarks(&default_path)?;
+        for mut bookmark in unnamed {
+            bookmark.set_collection(default_collection);
+            bookmarks.push(bookmark);
+        }
+        write_bookmarks(&default_path, &bookmarks)?;
+        remove_json(&unnamed_path)
+    }
+
+    /// Assign an id to every bookmark that does not have one, for
+    /// collections created before ids were introduced
+    fn backfill_ids(&self) -> Result<()> {
+        let _lock = lock_collection_dir(&self.dir_path)?;
+        let mut missing: Vec<(String, Vec<Bookmark>)> = Vec::new();
+        for json_path in self.get_json_paths()? {
+            let bookmarks = read_bookmarks(&json_path)?;
+            if bookmarks.iter().any(|bookmark| bookmark.get_id().is_empty()) {
+                missing.push((json_path, bookmarks));
+            }
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let mut ids = self.get_all_ids()?;
+        for (json_path, mut bookmarks) in missing {
+            for bookmark in bookmarks.iter_mut().filter(|bookmark| bookmark.get_id().is_empty()) {
+                let id = generate_id(&ids);
+                bookmark.set_id(&id);
+                ids.insert(id);
+            }
+            write_bookmarks(&json_path, &bookmarks)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the paths of all collection files in dir_path, sorted by file
+    /// name
+    ///
+    /// Hidden json files are not collections, broken collections are skipped.
+    fn get_json_paths(&self) -> Result<Vec<String>> {
+        let entries = read_dir(&self.dir_path).map_err(
+            |err| MemoireError::Io(self.dir_path.to_owned(), err)
+        )?;
+        let mut paths: Vec<String> = entries.filter_map(|entry| {
+            let entry = entry.ok()?;
+            let is_hidden = entry.file_name().to_str()?.starts_with('.');
+            let path = entry.path().into_os_string().into_string().ok()?;
+            let is_broken = self.broken.iter().any(
+                |collection| self.get_json_path(collection.get_name()) == path
+            );
+            if path.ends_with(".json") && !is_hidden && !is_broken {
+                Some(path)
+            } else {
+                None
+            }
+        }).collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Returns the ids of every bookmark in dir_path, trashed ones included
+    /// so a restored bookmark keeps a unique id
+    fn get_all_ids(&self) -> Result<HashSet<String>> {
+        let mut ids: HashSet<String> = HashSet::new();
+        for json_path in self.get_json_paths()? {
+            ids.extend(
+                read_bookmarks(&json_path)?.into_iter().map(|bookmark| bookmark.get_id().to_owned())
+            );
+        }
+        // A broken trash is reported on open, it does not stop bookmarks being added
+        if let Ok(trash) = self.read_trash() {
+            ids.extend(trash.iter().map(|trashed| trashed.get_bookmark().get_id().to_owned()));
+        }
+        Ok(ids)
+    }
+
+    /// Returns the path and bookmarks of the collection holding the bookmark
+    /// with id, along with its index
+    fn locate(&self, id: &str) -> Result<(String, Vec<Bookmark>, usize)> {
+        for json_path in self.get_json_paths()? {
+            let bookmarks = read_bookmarks(&json_path)?;
+            if let Some(index) = bookmarks.iter().position(|bookmark| bookmark.get_id() == id) {
+                return Ok((json_path, bookmarks, index));
+            }
+        }
+        Err(MemoireError::InvalidArgument(format!("No bookmark with id {}", id)))
+    }
+}
+
+
+/// Bookmarks being moved from one collection to another
+///
+/// Each file is replaced atomically, but a crash between the two writes
+/// would leave the bookmarks in both collections. The move is recorded before
+/// writing and completed the next time the store is opened.
+#[derive(Serialize, Deserialize)]
+struct PendingMove {
+    ids: Vec<String>,
+    from: String,
+    to: String,
+    // Whether the source collection is removed once the move is done
+    remove_source: bool,
+}
+
+
+impl PendingMove {
+    fn new(bookmarks: &[Bookmark], from: &str, to: &str, remove_source: bool) -> PendingMove {
+        PendingMove {
+            ids: bookmarks.iter().map(|bookmark| bookmark.get_id().to_owned()).collect(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+            remove_source
+        }
+    }
+}
+
+
+/// Read the list of bookmarks stored in json_path, a missing file is
+/// treated as an empty collection
+fn read_bookmarks(json_path: &str) -> Result<Vec<Bookmark>> {
+    if !Path::new(json_path).exists() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_json(json_path)?.0)
+}
+
+
+/// Parse the collection file at json_path, returns the bookmarks along with
+/// whether the file is in an older format
+fn parse_json(json_path: &str) -> Result<(Vec<Bookmark>, bool)> {
+    let content = read_to_string(json_path).map_err(
+        |err| MemoireError::Io(json_path.to_owned(), err)
+    )?;
+    parse_collection(json_path, &content, &get_collection_name(json_path))
+}
+
+
+/// Returns the collection stored in json_path, its file name
+fn get_collection_name(json_path: &str) -> String {
+    Path::new(json_path)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_owned()
+}
+
+
+fn collection_exists(collection: &str) -> MemoireError {
+    MemoireError::InvalidArgument(format!("Collection {} already exists", collection))
+}
+
+
+pub fn write_bookmarks(json_path: &str, bookmarks: &[Bookmark]) -> Result<()> {
+    let content = serde_json::to_string_pretty(&CollectionFile::new(bookmarks)).map_err(
+        |err| MemoireError::Json(json_path.to_owned(), err)
+    )?;
+    write_to_json(json_path, Some(&content))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::{TempDir, tempdir};
+
+    use super::*;
+
+
+    fn open_with_files(files: &[(&str, &str)], default_collection: &str) -> (TempDir, JsonStore) {
+        let dir = tempdir().unwrap();
+        for (name, content) in files {
+            write(dir.path().join(name), content).unwrap();
+        }
+        let store = JsonStore::open(dir.path().to_str().unwrap(), default_collection).unwrap();
+        (dir, store)
+    }
+
+
+    #[test]
+    fn open_adopts_legacy_unnamed_collection() {
+        let (dir, store) = open_with_files(
+            &[(".json", r#"[{"command": "ls -la", "annotation": "list", "tags": ["fs"], "collection": ""}]"#)],
+            "work"
+        );
+        let results = store.list("work").unwrap();
+        assert_eq!(results.len(), 1);
+        let bookmark = results[0].get_bookmark();
+        assert_eq!(bookmark.get_command(), "ls -la");
+        assert_eq!(bookmark.get_collection(), "work");
+        assert!(!bookmark.get_id().is_empty());
+        assert!(!dir.path().join(".json").exists());
+    }
+
+
+    #[test]
+    fn open_skips_broken_legacy_unnamed_collection() {
+        let (dir, store) = open_with_files(
+            &[
+                ("default.json", r#"[{"command": "pwd", "annotation": "", "tags": [], "collection": "default"}]"#),
+                (".json", r#"[{"command": 3}]"#)
+            ],
+            "default"
+        );
+        let broken = store.get_broken_collections();
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].get_name(), ".json");
+        assert!(matches!(broken[0].get_error(), MemoireError::InvalidCollection(_, _)));
+        assert_eq!(store.list("default").unwrap().len(), 1);
+        assert!(dir.path().join(".json").exists());
+    }
+
+
+    #[test]
+    fn open_appends_unnamed_collection_to_default_collection() {
+        let (_dir, store) = open_with_files(
+            &[
+                ("default.json", r#"[{"command": "pwd", "annotation": "", "tags": [], "collection": "default"}]"#),
+                (".json", r#"[{"command": "whoami", "annotation": "", "tags": [], "collection": ""}]"#)
+            ],
+            "default"
+        );
+        let commands: Vec<String> = store.list("default").unwrap().iter().map(
+            |result| result.get_bookmark().get_command().to_owned()
+        ).collect();
+        assert_eq!(commands, vec!["pwd", "whoami"]);
+    }
+
+
+    #[test]
+    fn open_warns_about_broken_trash() {
+        let (dir, store) = open_with_files(
+            &[
+                ("default.json", r#"[{"command": "pwd", "annotation": "", "tags": [], "collection": "default"}]"#),
+                (".trash.json", "[{")
+            ],
+            "default"
+        );
+        assert!(matches!(store.get_trash_error(), Some(MemoireError::BrokenTrash(_, _))));
+        let results = store.list("default").unwrap();
+        assert!(!results[0].get_bookmark().get_id().is_empty());
+        assert!(matches!(store.list_trash(), Err(MemoireError::BrokenTrash(_, _))));
+        assert!(matches!(store.restore(results[0].get_bookmark().get_id()), Err(MemoireError::BrokenTrash(_, _))));
+        assert_eq!(std::fs::read_to_string(dir.path().join(".trash.json")).unwrap(), "[{");
+    }
+}
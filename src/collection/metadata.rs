@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::store::Store;
+use crate::error::Result;
+
+
+/// Settings of a collection kept apart from its bookmarks
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CollectionMetadata {
+    // Whether the bookmarks of the collection are protected from changes
+    read_only: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    description: String,
+    // Person or team maintaining the collection
+    #[serde(skip_serializing_if = "String::is_empty")]
+    owner: String,
+    // Color of the collection in the TUI, a name like lightgreen or #rrggbb
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    // Shown before the collection name in the TUI
+    #[serde(skip_serializing_if = "String::is_empty")]
+    icon: String,
+    // Collections with a higher priority rank first in results
+    priority: i64,
+}
+
+
+impl CollectionMetadata {
+    pub fn get_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn get_description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn set_description(&mut self, description: &str) {
+        self.description = description.to_owned();
+    }
+
+    pub fn get_owner(&self) -> &str {
+        &self.owner
+    }
+
+    pub fn set_owner(&mut self, owner: &str) {
+        self.owner = owner.to_owned();
+    }
+
+    pub fn get_color(&self) -> Option<&str> {
+        self.color.as_deref()
+    }
+
+    pub fn set_color(&mut self, color: Option<&str>) {
+        self.color = color.map(|color| color.to_owned());
+    }
+
+    pub fn get_icon(&self) -> &str {
+        &self.icon
+    }
+
+    pub fn set_icon(&mut self, icon: &str) {
+        self.icon = icon.to_owned();
+    }
+
+    pub fn get_priority(&self) -> i64 {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: i64) {
+        self.priority = priority;
+    }
+
+    /// Returns the collection name preceded by the icon, if there is one
+    pub fn get_label(&self, collection: &str) -> String {
+        if self.icon.is_empty() {
+            collection.to_owned()
+        } else {
+            format!("{} {}", self.icon, collection)
+        }
+    }
+}
+
+
+/// Returns the metadata of every collection of store, by collection name
+pub fn load_all(store: &dyn Store) -> Result<HashMap<String, CollectionMetadata>> {
+    let mut metadata = HashMap::new();
+    for collection in store.collections()? {
+        let collection_metadata = store.get_metadata(&collection)?;
+        metadata.insert(collection, collection_metadata);
+    }
+    Ok(metadata)
+}
@@ -0,0 +1,233 @@
+use std::fs::read_to_string;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::bookmark::Bookmark;
+use super::store::Store;
+use super::validation::{Diagnostic, salvage_collection, validate_collection};
+use crate::error::{MemoireError, Result};
+
+
+/// Version of the collection file format written by this release
+///
+/// * `0` - A bare list of bookmarks without collection, `~/.memoir_history.json`
+/// * `1` - A bare list of bookmarks
+/// * `2` - A list of bookmarks wrapped in a versioned envelope
+pub const CURRENT_VERSION: u64 = 2;
+
+/// Upgrades the json of a collection file from the version at the same index
+/// to the next one
+const MIGRATIONS: [fn(Value, &str) -> Value; CURRENT_VERSION as usize] = [
+    fill_collection,
+    wrap_in_envelope
+];
+
+
+#[derive(Serialize, Deserialize)]
+pub struct CollectionFile {
+    version: u64,
+    bookmarks: Vec<Bookmark>,
+}
+
+
+impl CollectionFile {
+    pub fn new(bookmarks: &[Bookmark]) -> CollectionFile {
+        CollectionFile {
+            version: CURRENT_VERSION,
+            bookmarks: bookmarks.to_vec()
+        }
+    }
+}
+
+
+/// Parse the content of the collection file at json_path, upgrading it from
+/// older versions of the format
+///
+/// Returns the bookmarks along with whether the file needs to be rewritten
+/// in the current format. Fails with the location of every invalid bookmark,
+/// including bookmarks of another collection.
+///
+/// # Arguments
+///
+/// * `json_path` - A string slice that holds the path of the file, for errors
+/// * `content` - A string slice that holds the content of the file
+/// * `collection` - The collection of the file
+pub fn parse_collection(json_path: &str, content: &str, collection: &str) -> Result<(Vec<Bookmark>, bool)> {
+    parse(json_path, content, collection, true)
+}
+
+
+/// Parse the content of a collection file that is not part of the store,
+/// the bookmarks can belong to any collection
+pub fn parse_foreign_collection(json_path: &str, content: &str, collection: &str) -> Result<(Vec<Bookmark>, bool)> {
+    parse(json_path, content, collection, false)
+}
+
+
+fn parse(json_path: &str, content: &str, collection: &str, check_collection: bool) -> Result<(Vec<Bookmark>, bool)> {
+    let raw: Value = serde_json::from_str(content).map_err(
+        |err| MemoireError::InvalidCollection(json_path.to_owned(), vec![Diagnostic::from_json_error(&err)])
+    )?;
+    let version = get_version(&raw);
+    if version > CURRENT_VERSION {
+        return Err(MemoireError::UnsupportedVersion(json_path.to_owned(), version));
+    }
+    let diagnostics = validate_collection(content, version, collection, check_collection);
+    if !diagnostics.is_empty() {
+        return Err(MemoireError::InvalidCollection(json_path.to_owned(), diagnostics));
+    }
+    let raw = MIGRATIONS[version as usize..].iter().fold(
+        raw,
+        |raw, migration| migration(raw, collection)
+    );
+    let file: CollectionFile = serde_json::from_value(raw).map_err(
+        |err| MemoireError::Json(json_path.to_owned(), err)
+    )?;
+    Ok((file.bookmarks, version < CURRENT_VERSION))
+}
+
+
+/// Keep the valid bookmarks of the collection file at json_path, see
+/// salvage_collection
+///
+/// Fails if the file is not json or uses a newer format version.
+pub fn salvage(json_path: &str, content: &str, collection: &str) -> Result<(Vec<Bookmark>, Vec<Diagnostic>)> {
+    let raw: Value = serde_json::from_str(content).map_err(
+        |err| MemoireError::InvalidCollection(json_path.to_owned(), vec![Diagnostic::from_json_error(&err)])
+    )?;
+    let version = get_version(&raw);
+    if version > CURRENT_VERSION {
+        return Err(MemoireError::UnsupportedVersion(json_path.to_owned(), version));
+    }
+    salvage_collection(content, version, collection).map_err(
+        |diagnostic| MemoireError::InvalidCollection(json_path.to_owned(), vec![diagnostic])
+    )
+}
+
+
+/// Import the bookmarks of the history file used before 0.1.2 into
+/// collection
+///
+/// Returns the number of imported bookmarks
+pub fn import_legacy_history(store: &dyn Store, history_path: &str, collection: &str) -> Result<usize> {
+    let content = read_to_string(history_path).map_err(
+        |err| MemoireError::Io(history_path.to_owned(), err)
+    )?;
+    let (bookmarks, _) = parse_foreign_collection(history_path, &content, collection)?;
+    for bookmark in bookmarks.iter() {
+        let mut bookmark = bookmark.to_owned();
+        bookmark.set_collection(collection);
+        store.add(&bookmark, None)?;
+    }
+    Ok(bookmarks.len())
+}
+
+
+/// Returns the format version of raw, files written before the format was
+/// versioned are bare lists
+fn get_version(raw: &Value) -> u64 {
+    match raw {
+        Value::Array(_) => 0,
+        // Let deserialization report envelopes without a valid version
+        _ => raw.get("version").and_then(Value::as_u64).unwrap_or(CURRENT_VERSION)
+    }
+}
+
+
+/// 0 -> 1: Assign the collection of the file to bookmarks that have none
+fn fill_collection(mut raw: Value, collection: &str) -> Value {
+    if let Value::Array(bookmarks) = &mut raw {
+        for bookmark in bookmarks.iter_mut() {
+            if let Value::Object(fields) = bookmark {
+                fields.entry("collection").or_insert_with(|| Value::from(collection));
+            }
+        }
+    }
+    raw
+}
+
+
+/// 1 -> 2: Wrap the list of bookmarks in a versioned envelope
+fn wrap_in_envelope(raw: Value, _collection: &str) -> Value {
+    serde_json::json!({
+        "version": 2,
+        "bookmarks": raw
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::json::JsonStore;
+
+
+    #[test]
+    fn parse_upgrades_list_without_collection() {
+        let (bookmarks, outdated) = parse_collection(
+            "tools.json", r#"[{"command": "top", "annotation": "", "tags": []}]"#, "tools"
+        ).unwrap();
+        assert!(outdated);
+        assert_eq!(bookmarks[0].get_collection(), "tools");
+    }
+
+
+    #[test]
+    fn parse_upgrades_bare_list() {
+        let (bookmarks, outdated) = parse_collection(
+            "tools.json", r#"[{"command": "top", "annotation": "", "tags": [], "collection": "tools"}]"#, "tools"
+        ).unwrap();
+        assert!(outdated);
+        assert_eq!(bookmarks[0].get_command(), "top");
+    }
+
+
+    #[test]
+    fn parse_keeps_current_envelope() {
+        let content = serde_json::to_string(
+            &CollectionFile::new(&[Bookmark::new("top", "", &Vec::new(), "tools")])
+        ).unwrap();
+        let (bookmarks, outdated) = parse_collection("tools.json", &content, "tools").unwrap();
+        assert!(!outdated);
+        assert_eq!(bookmarks.len(), 1);
+    }
+
+
+    #[test]
+    fn parse_rejects_newer_version() {
+        assert!(matches!(
+            parse_collection("tools.json", r#"{"version": 3, "bookmarks": []}"#, "tools"),
+            Err(MemoireError::UnsupportedVersion(_, 3))
+        ));
+    }
+
+
+    #[test]
+    fn parse_checks_collection_unless_foreign() {
+        let content = r#"[{"command": "top", "annotation": "", "tags": [], "collection": ""}]"#;
+        assert!(matches!(
+            parse_collection("tools.json", content, "tools"),
+            Err(MemoireError::InvalidCollection(_, _))
+        ));
+        assert!(parse_foreign_collection("tools.json", content, "tools").is_ok());
+    }
+
+
+    #[test]
+    fn import_legacy_history_adds_to_collection() {
+        let dir = tempdir().unwrap();
+        let history_path = dir.path().join(".memoir_history.json");
+        write(&history_path, r#"[{"command": "ls", "annotation": "list", "tags": ["fs"]}]"#).unwrap();
+        let store = JsonStore::open(dir.path().join("data").to_str().unwrap(), "default").unwrap();
+        let count = import_legacy_history(&store, history_path.to_str().unwrap(), "legacy").unwrap();
+        assert_eq!(count, 1);
+        let results = store.list("legacy").unwrap();
+        assert_eq!(results[0].get_bookmark().get_annotation(), "list");
+        assert_eq!(results[0].get_bookmark().get_collection(), "legacy");
+    }
+}
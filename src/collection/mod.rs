@@ -1,3 +1,11 @@
 pub mod bookmark;
 pub mod util;
-pub mod jq;
\ No newline at end of file
+pub mod store;
+pub mod json;
+pub mod migration;
+pub mod sqlite;
+pub mod validation;
+pub mod doctor;
+pub mod journal;
+pub mod dedupe;
+pub mod metadata;
@@ -0,0 +1,825 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use rusqlite::{named_params, params, Batch, Connection, OptionalExtension, Row, Transaction, TransactionBehavior};
+
+use super::bookmark::Bookmark;
+use super::journal::{JournalEntry, Operation};
+use super::json::JsonStore;
+use super::metadata::CollectionMetadata;
+use super::store::{
+    BrokenCollection, SearchMode, SearchResult, Store, TrashedBookmark, generate_id, validate_collection_name
+};
+use super::util::{LOCK_TIMEOUT, create_collection_dir};
+use crate::error::{MemoireError, Result};
+
+
+pub static DB_FILE_NAME: &str = "memoire.db";
+// Keywords shorter than a trigram can not use the full text index
+const MIN_INDEXED_KEYWORD_LENGTH: usize = 3;
+
+static SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS bookmarks (
+        id TEXT NOT NULL UNIQUE,
+        collection TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        annotation TEXT NOT NULL,
+        tags TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS bookmarks_position ON bookmarks (collection, position);
+    CREATE VIRTUAL TABLE IF NOT EXISTS bookmarks_fts USING fts5 (
+        command, annotation, tags, collection,
+        tokenize = 'trigram case_sensitive 1'
+    );
+";
+
+/// Upgrades the schema from the user_version at the same index to the next one,
+/// `:default_collection` is bound to the collection of bookmarks added without one
+static SCHEMA_MIGRATIONS: [&str; 5] = [
+    "
+    ALTER TABLE bookmarks ADD COLUMN created_at TEXT;
+    ALTER TABLE bookmarks ADD COLUMN updated_at TEXT;
+    ALTER TABLE bookmarks ADD COLUMN last_used_at TEXT;
+    ALTER TABLE bookmarks ADD COLUMN use_count INTEGER NOT NULL DEFAULT 0;
+    ",
+    // Keep empty collections, and move bookmarks added without a collection
+    // to the end of the default one
+    "
+    CREATE TABLE collections (name TEXT PRIMARY KEY);
+    CREATE TEMP TABLE default_size AS
+        SELECT COUNT(*) AS size FROM bookmarks WHERE collection = :default_collection;
+    UPDATE bookmarks_fts SET collection = :default_collection
+        WHERE rowid IN (SELECT rowid FROM bookmarks WHERE collection = '');
+    UPDATE bookmarks
+        SET collection = :default_collection, position = position + (SELECT size FROM default_size)
+        WHERE collection = '';
+    DROP TABLE default_size;
+    INSERT INTO collections (name) SELECT DISTINCT collection FROM bookmarks;
+    ",
+    "
+    CREATE TABLE trash (
+        id TEXT NOT NULL UNIQUE,
+        collection TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        command TEXT NOT NULL,
+        annotation TEXT NOT NULL,
+        tags TEXT NOT NULL,
+        created_at TEXT,
+        updated_at TEXT,
+        last_used_at TEXT,
+        use_count INTEGER NOT NULL DEFAULT 0,
+        deleted_at TEXT NOT NULL
+    );
+    ",
+    // Entries are kept as json, they are only ever read back whole
+    "
+    CREATE TABLE journal (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        entry TEXT NOT NULL
+    );
+    ",
+    // Metadata is kept as json, like journal entries
+    "
+    ALTER TABLE collections ADD COLUMN metadata TEXT NOT NULL DEFAULT '{}';
+    ",
+];
+
+static SELECT_BOOKMARKS: &str = "
+    SELECT b.id, b.command, b.annotation, b.tags, b.collection, b.position,
+           b.created_at, b.updated_at, b.last_used_at, b.use_count
+    FROM bookmarks b
+";
+
+static SELECT_TRASH: &str = "
+    SELECT id, command, annotation, tags, collection, position,
+           created_at, updated_at, last_used_at, use_count, deleted_at
+    FROM trash
+";
+
+
+/// Store keeping every collection in a single sqlite database at
+/// `<dir_path>/memoire.db`, with a trigram index for search
+pub struct SqliteStore {
+    conn: Connection,
+    // Json collections that could not be imported
+    broken: Vec<BrokenCollection>
+}
+
+
+impl Store for SqliteStore {
+    fn collections(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT name FROM collections ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<Vec<String>>>()?)
+    }
+
+    fn get_broken_collections(&self) -> &[BrokenCollection] {
+        &self.broken
+    }
+
+    fn get_trash_error(&self) -> Option<&MemoireError> {
+        // Trashed bookmarks are rows of the database
+        None
+    }
+
+    fn create_collection(&self, name: &str) -> Result<()> {
+        validate_collection_name(name)?;
+        let tx = self.begin_write()?;
+        if has_collection(&tx, name)? {
+            return Err(collection_exists(name));
+        }
+        insert_collection(&tx, name)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn rename_collection(&self, from: &str, to: &str) -> Result<usize> {
+        validate_collection_name(to)?;
+        let tx = self.begin_write()?;
+        ensure_collection(&tx, from)?;
+        ensure_writable(&tx, from)?;
+        if has_collection(&tx, to)? {
+            return Err(collection_exists(to));
+        }
+        tx.execute(
+            "UPDATE bookmarks_fts SET collection = ?2
+             WHERE rowid IN (SELECT rowid FROM bookmarks WHERE collection = ?1)",
+            params![from, to]
+        )?;
+        let count = tx.execute(
+            "UPDATE bookmarks SET collection = ?2 WHERE collection = ?1",
+            params![from, to]
+        )?;
+        tx.execute("UPDATE collections SET name = ?2 WHERE name = ?1", params![from, to])?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    fn merge_collections(&self, from: &str, into: &str) -> Result<usize> {
+        if from == into {
+            return Err(MemoireError::InvalidArgument(
+                format!("Can not merge collection {} into itself", from)
+            ));
+        }
+        let tx = self.begin_write()?;
+        ensure_collection(&tx, from)?;
+        ensure_collection(&tx, into)?;
+        ensure_writable(&tx, from)?;
+        ensure_writable(&tx, into)?;
+        let size = get_collection_size(&tx, into)?;
+        tx.execute(
+            "UPDATE bookmarks_fts SET collection = ?2
+             WHERE rowid IN (SELECT rowid FROM bookmarks WHERE collection = ?1)",
+            params![from, into]
+        )?;
+        let count = tx.execute(
+            "UPDATE bookmarks SET collection = ?2, position = position + ?3 WHERE collection = ?1",
+            params![from, into, size as i64]
+        )?;
+        tx.execute("DELETE FROM collections WHERE name = ?1", params![from])?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    fn delete_collection(&self, name: &str) -> Result<usize> {
+        let tx = self.begin_write()?;
+        ensure_collection(&tx, name)?;
+        ensure_writable(&tx, name)?;
+        for result in self.list(name)? {
+            record(&tx, &JournalEntry::new(Operation::Delete, Some(&result), None))?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO trash (
+                id, collection, position, command, annotation, tags,
+                created_at, updated_at, last_used_at, use_count, deleted_at
+             ) SELECT id, collection, position, command, annotation, tags,
+                      created_at, updated_at, last_used_at, use_count, ?2
+             FROM bookmarks WHERE collection = ?1",
+            params![name, Utc::now()]
+        )?;
+        tx.execute(
+            "DELETE FROM bookmarks_fts
+             WHERE rowid IN (SELECT rowid FROM bookmarks WHERE collection = ?1)",
+            params![name]
+        )?;
+        let count = tx.execute("DELETE FROM bookmarks WHERE collection = ?1", params![name])?;
+        tx.execute("DELETE FROM collections WHERE name = ?1", params![name])?;
+        tx.commit()?;
+        Ok(count)
+    }
+
+    fn get_metadata(&self, collection: &str) -> Result<CollectionMetadata> {
+        read_metadata(&self.conn, collection)
+    }
+
+    fn set_metadata(&self, collection: &str, metadata: &CollectionMetadata) -> Result<()> {
+        let tx = self.begin_write()?;
+        ensure_collection(&tx, collection)?;
+        let metadata = serde_json::to_string(metadata).map_err(
+            |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+        )?;
+        tx.execute("UPDATE collections SET metadata = ?2 WHERE name = ?1", params![collection, metadata])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn is_read_only(&self, collection: &str) -> Result<bool> {
+        Ok(self.get_metadata(collection)?.get_read_only())
+    }
+
+    fn list(&self, collection: &str) -> Result<Vec<SearchResult>> {
+        self.query(
+            &format!("{} WHERE b.collection = ?1 ORDER BY b.position", SELECT_BOOKMARKS),
+            &[collection]
+        )
+    }
+
+    fn search(&self, keywords: &[&str], mode: SearchMode) -> Result<Vec<SearchResult>> {
+        // An empty keyword is contained in every bookmark
+        let has_empty = keywords.iter().any(|keyword| keyword.is_empty());
+        let keywords: Vec<&str> = keywords.iter().copied().filter(|keyword| !keyword.is_empty()).collect();
+        if keywords.is_empty() || (has_empty && mode == SearchMode::Union) {
+            return self.query(
+                &format!("{} ORDER BY b.collection, b.position", SELECT_BOOKMARKS),
+                &[]
+            );
+        }
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<String> = Vec::new();
+        for keyword in keywords {
+            values.push(keyword.to_string());
+            let param = values.len();
+            if keyword.chars().count() >= MIN_INDEXED_KEYWORD_LENGTH {
+                // Quote the keyword so it is matched as a phrase
+                values[param - 1] = format!("\"{}\"", keyword.replace('"', "\"\""));
+                conditions.push(format!(
+                    "b.rowid IN (SELECT rowid FROM bookmarks_fts WHERE bookmarks_fts MATCH ?{})",
+                    param
+                ));
+            } else {
+                conditions.push(format!(
+                    "(instr(f.command, ?{0}) OR instr(f.annotation, ?{0}) \
+                      OR instr(f.tags, ?{0}) OR instr(f.collection, ?{0}))",
+                    param
+                ));
+            }
+        }
+        self.query(
+            &format!(
+                "{} JOIN bookmarks_fts f ON f.rowid = b.rowid WHERE {} ORDER BY b.collection, b.position",
+                SELECT_BOOKMARKS,
+                conditions.join(match mode {
+                    SearchMode::Union => " OR ",
+                    SearchMode::Intersection => " AND "
+                })
+            ),
+            &values.iter().map(|value| value.as_str()).collect::<Vec<&str>>()
+        )
+    }
+
+    fn find(&self, id: &str) -> Result<Option<SearchResult>> {
+        Ok(self.query(&format!("{} WHERE b.id = ?1", SELECT_BOOKMARKS), &[id])?.pop())
+    }
+
+    fn add(&self, bookmark: &Bookmark, index: Option<usize>) -> Result<String> {
+        let tx = self.begin_write()?;
+        ensure_writable(&tx, bookmark.get_collection())?;
+        let mut bookmark = bookmark.to_owned();
+        if bookmark.get_id().is_empty() {
+            bookmark.set_id(&generate_id(&get_all_ids(&tx)?));
+        }
+        bookmark.mark_created();
+        if !has_collection(&tx, bookmark.get_collection())? {
+            validate_collection_name(bookmark.get_collection())?;
+        }
+        let size = get_collection_size(&tx, bookmark.get_collection())?;
+        let position = index.unwrap_or(size).min(size);
+        open_gap(&tx, bookmark.get_collection(), position)?;
+        insert(&tx, &bookmark, position)?;
+        let id = bookmark.get_id().to_owned();
+        let new = SearchResult::new(position, bookmark);
+        record(&tx, &JournalEntry::new(Operation::Add, None, Some(&new)))?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn update(&self, id: &str, bookmark: &Bookmark) -> Result<()> {
+        let tx = self.begin_write()?;
+        let old = locate(&tx, id)?;
+        ensure_writable(&tx, old.get_bookmark().get_collection())?;
+        ensure_writable(&tx, bookmark.get_collection())?;
+        let mut bookmark = bookmark.to_owned();
+        bookmark.set_id(id);
+        bookmark.mark_updated(old.get_bookmark());
+        let mut position = old.get_index();
+        if old.get_bookmark().get_collection() != bookmark.get_collection() {
+            if !has_collection(&tx, bookmark.get_collection())? {
+                validate_collection_name(bookmark.get_collection())?;
+            }
+            close_gap(&tx, old.get_bookmark().get_collection(), position)?;
+            position = get_collection_size(&tx, bookmark.get_collection())?;
+        }
+        remove(&tx, id)?;
+        insert(&tx, &bookmark, position)?;
+        let new = SearchResult::new(position, bookmark);
+        record(&tx, &JournalEntry::new(Operation::Edit, Some(&old), Some(&new)))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn mark_used(&self, id: &str) -> Result<()> {
+        let tx = self.begin_write()?;
+        let mut bookmark = locate(&tx, id)?.get_bookmark().to_owned();
+        if read_metadata(&tx, bookmark.get_collection())?.get_read_only() {
+            return Ok(());
+        }
+        bookmark.mark_used();
+        tx.execute(
+            "UPDATE bookmarks SET last_used_at = ?1, use_count = ?2 WHERE id = ?3",
+            params![bookmark.get_last_used_at(), bookmark.get_use_count() as i64, id]
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<SearchResult> {
+        let tx = self.begin_write()?;
+        let old = locate(&tx, id)?;
+        ensure_writable(&tx, old.get_bookmark().get_collection())?;
+        insert_trash(&tx, &TrashedBookmark::new(old.get_index(), old.get_bookmark().to_owned()))?;
+        remove(&tx, id)?;
+        close_gap(&tx, old.get_bookmark().get_collection(), old.get_index())?;
+        record(&tx, &JournalEntry::new(Operation::Delete, Some(&old), None))?;
+        tx.commit()?;
+        Ok(old)
+    }
+
+    fn list_trash(&self) -> Result<Vec<TrashedBookmark>> {
+        let mut stmt = self.conn.prepare(&format!("{} ORDER BY deleted_at DESC, rowid DESC", SELECT_TRASH))?;
+        let rows = stmt.query_map([], row_to_trashed_bookmark)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<TrashedBookmark>>>()?)
+    }
+
+    fn restore(&self, id: &str) -> Result<SearchResult> {
+        let tx = self.begin_write()?;
+        let trashed = tx.query_row(
+            &format!("{} WHERE id = ?1", SELECT_TRASH),
+            params![id],
+            row_to_trashed_bookmark
+        ).optional()?.ok_or_else(
+            || MemoireError::InvalidArgument(format!("No bookmark with id {} in the trash", id))
+        )?;
+        let bookmark = trashed.get_bookmark();
+        ensure_writable(&tx, bookmark.get_collection())?;
+        if !has_collection(&tx, bookmark.get_collection())? {
+            validate_collection_name(bookmark.get_collection())?;
+        }
+        let position = trashed.get_index().min(get_collection_size(&tx, bookmark.get_collection())?);
+        open_gap(&tx, bookmark.get_collection(), position)?;
+        insert(&tx, bookmark, position)?;
+        tx.execute("DELETE FROM trash WHERE id = ?1", params![id])?;
+        let result = SearchResult::new(position, bookmark.to_owned());
+        record(&tx, &JournalEntry::new(Operation::Restore, None, Some(&result)))?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    fn empty_trash(&self) -> Result<usize> {
+        Ok(self.conn.execute("DELETE FROM trash", [])?)
+    }
+
+    fn move_bookmark(&self, id: &str, to: usize) -> Result<usize> {
+        let tx = self.begin_write()?;
+        let old = locate(&tx, id)?;
+        let collection = old.get_bookmark().get_collection();
+        ensure_writable(&tx, collection)?;
+        remove(&tx, id)?;
+        close_gap(&tx, collection, old.get_index())?;
+        let to = to.min(get_collection_size(&tx, collection)?);
+        open_gap(&tx, collection, to)?;
+        insert(&tx, old.get_bookmark(), to)?;
+        let new = SearchResult::new(to, old.get_bookmark().to_owned());
+        record(&tx, &JournalEntry::new(Operation::Move, Some(&old), Some(&new)))?;
+        tx.commit()?;
+        Ok(to)
+    }
+
+    fn log(&self) -> Result<Vec<JournalEntry>> {
+        let mut stmt = self.conn.prepare("SELECT id, entry FROM journal ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?;
+        let mut entries: Vec<JournalEntry> = Vec::new();
+        for row in rows {
+            let (id, entry) = row?;
+            let mut entry: JournalEntry = serde_json::from_str(&entry).map_err(
+                |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+            )?;
+            entry.set_id(id as u64);
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+}
+
+
+impl SqliteStore {
+    /// Open the sqlite store in dir_path, creating the database if needed
+    ///
+    /// A newly created database is filled with the json collections, trash
+    /// and journal found in dir_path, so switching stores keeps existing bookmarks.
+    /// Json bookmarks saved without a collection are imported into
+    /// default_collection.
+    pub fn open(dir_path: &str, default_collection: &str) -> Result<SqliteStore> {
+        create_collection_dir(dir_path)?;
+        let conn = Connection::open(format!("{}/{}", dir_path, DB_FILE_NAME))?;
+        // Wait for other memoire processes like the json store does
+        conn.busy_timeout(LOCK_TIMEOUT)?;
+        // The schema is created along with the import, a failed import leaves
+        // the database without tables and is tried again on the next open
+        let tx = Transaction::new_unchecked(&conn, TransactionBehavior::Immediate)?;
+        let is_new = !has_table(&tx, "bookmarks")?;
+        tx.execute_batch(SCHEMA)?;
+        migrate_schema(&tx, default_collection)?;
+        let broken = if is_new {
+            import_jsons(&tx, dir_path, default_collection)?
+        } else {
+            Vec::new()
+        };
+        tx.commit()?;
+        Ok(
+            SqliteStore {
+                conn,
+                broken
+            }
+        )
+    }
+
+    /// Begin a transaction holding the write lock from the start, so another
+    /// process can not write between its reads and writes
+    fn begin_write(&self) -> Result<Transaction<'_>> {
+        Ok(Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?)
+    }
+
+    fn query(&self, sql: &str, values: &[&str]) -> Result<Vec<SearchResult>> {
+        let mut stmt = self.conn.prepare(sql)?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(values), row_to_search_result)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<SearchResult>>>()?)
+    }
+}
+
+
+/// Upgrade the schema to the last migration, within the transaction of tx
+fn migrate_schema(tx: &Transaction, default_collection: &str) -> Result<()> {
+    let version: i64 = tx.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in SCHEMA_MIGRATIONS.iter().enumerate().skip(version as usize) {
+        // Statements are run one by one, execute_batch can not bind parameters
+        let mut batch = Batch::new(tx, migration);
+        while let Some(mut stmt) = batch.next()? {
+            match stmt.parameter_index(":default_collection")? {
+                Some(_) => stmt.execute(named_params! {":default_collection": default_collection})?,
+                None => stmt.execute([])?
+            };
+        }
+        tx.execute_batch(&format!("PRAGMA user_version = {}", index + 1))?;
+    }
+    Ok(())
+}
+
+
+/// Import the json collections, trash and journal in dir_path, returns the
+/// collections that could not be loaded
+fn import_jsons(tx: &Transaction, dir_path: &str, default_collection: &str) -> Result<Vec<BrokenCollection>> {
+    let json_store = JsonStore::open(dir_path, default_collection)?;
+    for collection in json_store.collections()? {
+        insert_collection(tx, &collection)?;
+        let metadata = serde_json::to_string(&json_store.get_metadata(&collection)?).map_err(
+            |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+        )?;
+        tx.execute("UPDATE collections SET metadata = ?2 WHERE name = ?1", params![collection, metadata])?;
+        for result in json_store.list(&collection)? {
+            insert(tx, result.get_bookmark(), result.get_index())?;
+        }
+    }
+    for trashed in json_store.list_trash()? {
+        insert_trash(tx, &trashed)?;
+    }
+    for entry in json_store.log()? {
+        record(tx, &entry)?;
+    }
+    Ok(json_store.into_broken_collections())
+}
+
+
+fn has_table(tx: &Transaction, name: &str) -> Result<bool> {
+    Ok(
+        tx.query_row(
+            "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+            params![name],
+            |_row| Ok(())
+        ).optional()?.is_some()
+    )
+}
+
+
+fn row_to_search_result(row: &Row) -> rusqlite::Result<SearchResult> {
+    let tags: String = row.get(3)?;
+    let mut bookmark = Bookmark::new(
+        &row.get::<_, String>(1)?,
+        &row.get::<_, String>(2)?,
+        &serde_json::from_str(&tags).unwrap_or_default(),
+        &row.get::<_, String>(4)?
+    );
+    bookmark.set_id(&row.get::<_, String>(0)?);
+    bookmark.set_usage(row.get(6)?, row.get(7)?, row.get(8)?, row.get::<_, i64>(9)? as u64);
+    Ok(SearchResult::new(row.get::<_, i64>(5)? as usize, bookmark))
+}
+
+
+fn row_to_trashed_bookmark(row: &Row) -> rusqlite::Result<TrashedBookmark> {
+    let result = row_to_search_result(row)?;
+    Ok(TrashedBookmark::with_deleted_at(result.get_index(), result.get_bookmark().to_owned(), row.get(10)?))
+}
+
+
+/// Returns the ids of every bookmark, trashed ones included so a restored
+/// bookmark keeps a unique id
+fn get_all_ids(tx: &Transaction) -> Result<HashSet<String>> {
+    let mut stmt = tx.prepare("SELECT id FROM bookmarks UNION SELECT id FROM trash")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    Ok(rows.collect::<rusqlite::Result<HashSet<String>>>()?)
+}
+
+
+fn has_collection(tx: &Transaction, name: &str) -> Result<bool> {
+    Ok(
+        tx.query_row(
+            "SELECT 1 FROM collections WHERE name = ?1",
+            params![name],
+            |_row| Ok(())
+        ).optional()?.is_some()
+    )
+}
+
+
+/// Fails if there is no collection named name
+fn ensure_collection(tx: &Transaction, name: &str) -> Result<()> {
+    if has_collection(tx, name)? {
+        Ok(())
+    } else {
+        Err(MemoireError::InvalidArgument(format!("No collection named {}", name)))
+    }
+}
+
+
+/// Returns the metadata of collection, the defaults if it does not exist
+fn read_metadata(conn: &Connection, collection: &str) -> Result<CollectionMetadata> {
+    let metadata: Option<String> = conn.query_row(
+        "SELECT metadata FROM collections WHERE name = ?1",
+        params![collection],
+        |row| row.get(0)
+    ).optional()?;
+    match metadata {
+        Some(metadata) => serde_json::from_str(&metadata).map_err(
+            |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+        ),
+        None => Ok(CollectionMetadata::default())
+    }
+}
+
+
+/// Fails with MemoireError::ReadOnly if collection is read-only
+fn ensure_writable(conn: &Connection, collection: &str) -> Result<()> {
+    if read_metadata(conn, collection)?.get_read_only() {
+        return Err(MemoireError::ReadOnly(collection.to_owned()));
+    }
+    Ok(())
+}
+
+
+fn collection_exists(name: &str) -> MemoireError {
+    MemoireError::InvalidArgument(format!("Collection {} already exists", name))
+}
+
+
+fn insert_collection(tx: &Transaction, name: &str) -> Result<()> {
+    tx.execute("INSERT OR IGNORE INTO collections (name) VALUES (?1)", params![name])?;
+    Ok(())
+}
+
+
+fn get_collection_size(tx: &Transaction, collection: &str) -> Result<usize> {
+    let size: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM bookmarks WHERE collection = ?1",
+        params![collection],
+        |row| row.get(0)
+    )?;
+    Ok(size as usize)
+}
+
+
+/// Returns the bookmark with id, fails if there is none
+fn locate(tx: &Transaction, id: &str) -> Result<SearchResult> {
+    tx.query_row(
+        &format!("{} WHERE b.id = ?1", SELECT_BOOKMARKS),
+        params![id],
+        row_to_search_result
+    ).optional()?.ok_or_else(
+        || MemoireError::InvalidArgument(format!("No bookmark with id {}", id))
+    )
+}
+
+
+/// Shift the bookmarks at or after position one down to make room
+fn open_gap(tx: &Transaction, collection: &str, position: usize) -> Result<()> {
+    tx.execute(
+        "UPDATE bookmarks SET position = position + 1 WHERE collection = ?1 AND position >= ?2",
+        params![collection, position as i64]
+    )?;
+    Ok(())
+}
+
+
+/// Shift the bookmarks after position one up to fill the removed slot
+fn close_gap(tx: &Transaction, collection: &str, position: usize) -> Result<()> {
+    tx.execute(
+        "UPDATE bookmarks SET position = position - 1 WHERE collection = ?1 AND position > ?2",
+        params![collection, position as i64]
+    )?;
+    Ok(())
+}
+
+
+/// Insert bookmark at position, creating its collection if needed
+fn insert(tx: &Transaction, bookmark: &Bookmark, position: usize) -> Result<()> {
+    insert_collection(tx, bookmark.get_collection())?;
+    let tags = serde_json::to_string(bookmark.get_tags()).map_err(
+        |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+    )?;
+    tx.execute(
+        "INSERT INTO bookmarks (
+            id, collection, position, command, annotation, tags,
+            created_at, updated_at, last_used_at, use_count
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            bookmark.get_id(),
+            bookmark.get_collection(),
+            position as i64,
+            bookmark.get_command(),
+            bookmark.get_annotation(),
+            tags,
+            bookmark.get_created_at(),
+            bookmark.get_updated_at(),
+            bookmark.get_last_used_at(),
+            bookmark.get_use_count() as i64
+        ]
+    )?;
+    tx.execute(
+        "INSERT INTO bookmarks_fts (rowid, command, annotation, tags, collection)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            tx.last_insert_rowid(),
+            bookmark.get_command(),
+            bookmark.get_annotation(),
+            bookmark.get_tags_as_string("\n"),
+            bookmark.get_collection()
+        ]
+    )?;
+    Ok(())
+}
+
+
+fn insert_trash(tx: &Transaction, trashed: &TrashedBookmark) -> Result<()> {
+    let bookmark = trashed.get_bookmark();
+    let tags = serde_json::to_string(bookmark.get_tags()).map_err(
+        |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+    )?;
+    // Replaces a trashed bookmark that had the same id
+    tx.execute(
+        "INSERT OR REPLACE INTO trash (
+            id, collection, position, command, annotation, tags,
+            created_at, updated_at, last_used_at, use_count, deleted_at
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            bookmark.get_id(),
+            bookmark.get_collection(),
+            trashed.get_index() as i64,
+            bookmark.get_command(),
+            bookmark.get_annotation(),
+            tags,
+            bookmark.get_created_at(),
+            bookmark.get_updated_at(),
+            bookmark.get_last_used_at(),
+            bookmark.get_use_count() as i64,
+            trashed.get_deleted_at()
+        ]
+    )?;
+    Ok(())
+}
+
+
+/// Append entry to the journal, numbered after the last one
+fn record(tx: &Transaction, entry: &JournalEntry) -> Result<()> {
+    let entry = serde_json::to_string(entry).map_err(
+        |err| MemoireError::Json(DB_FILE_NAME.to_owned(), err)
+    )?;
+    tx.execute("INSERT INTO journal (entry) VALUES (?1)", params![entry])?;
+    Ok(())
+}
+
+
+fn remove(tx: &Transaction, id: &str) -> Result<()> {
+    tx.execute(
+        "DELETE FROM bookmarks_fts WHERE rowid = (SELECT rowid FROM bookmarks WHERE id = ?1)",
+        params![id]
+    )?;
+    tx.execute("DELETE FROM bookmarks WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{remove_file, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+
+    #[test]
+    fn open_imports_json_collections() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("tools.json"),
+            r#"[{"command": "top", "annotation": "processes", "tags": ["sys"], "collection": "tools"}]"#
+        ).unwrap();
+        let store = SqliteStore::open(dir.path().to_str().unwrap(), "default").unwrap();
+        assert_eq!(store.collections().unwrap(), vec!["tools"]);
+        let results = store.list("tools").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get_bookmark().get_command(), "top");
+        assert_eq!(results[0].get_bookmark().get_tags(), &vec!["sys".to_owned()]);
+    }
+
+
+    #[test]
+    fn open_retries_failed_import() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+        write(
+            dir.path().join("tools.json"),
+            r#"[{"command": "top", "annotation": "", "tags": [], "collection": "tools"}]"#
+        ).unwrap();
+        write(dir.path().join(".json"), r#"[{"command": "ls", "annotation": "", "tags": [], "collection": ""}]"#).unwrap();
+        write(dir.path().join(".pending_move.json"), "not json").unwrap();
+        assert!(SqliteStore::open(dir_path, "default").is_err());
+
+        remove_file(dir.path().join(".pending_move.json")).unwrap();
+        let store = SqliteStore::open(dir_path, "default").unwrap();
+        assert_eq!(store.collections().unwrap(), vec!["default", "tools"]);
+        assert_eq!(store.list("tools").unwrap().len(), 1);
+        assert_eq!(store.list("default").unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn concurrent_writers_wait_for_each_other() {
+        let dir = tempdir().unwrap();
+        let dir_path = dir.path().to_str().unwrap().to_owned();
+        let first = SqliteStore::open(&dir_path, "default").unwrap();
+        let tx = first.begin_write().unwrap();
+        let writer = std::thread::spawn(move || {
+            let second = SqliteStore::open(&dir_path, "default").unwrap();
+            second.add(&Bookmark::new("top", "", &Vec::new(), "tools"), None).unwrap();
+        });
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        tx.commit().unwrap();
+        writer.join().unwrap();
+        assert_eq!(first.list("tools").unwrap().len(), 1);
+    }
+
+
+    #[test]
+    fn migration_moves_unnamed_bookmarks_to_default_collection() {
+        let dir = tempdir().unwrap();
+        let conn = Connection::open(dir.path().join(DB_FILE_NAME)).unwrap();
+        // A database created before collections were kept, at user_version 1
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute_batch(SCHEMA_MIGRATIONS[0]).unwrap();
+        conn.execute_batch("
+            PRAGMA user_version = 1;
+            INSERT INTO bookmarks (id, collection, position, command, annotation, tags)
+                VALUES ('a', 'work', 0, 'pwd', '', '[]'), ('b', '', 0, 'whoami', '', '[]');
+            INSERT INTO bookmarks_fts (rowid, command, annotation, tags, collection)
+                SELECT rowid, command, annotation, tags, collection FROM bookmarks;
+        ").unwrap();
+        drop(conn);
+
+        let store = SqliteStore::open(dir.path().to_str().unwrap(), "work").unwrap();
+        assert_eq!(store.collections().unwrap(), vec!["work"]);
+        let results = store.list("work").unwrap();
+        let commands: Vec<&str> = results.iter().map(|result| result.get_bookmark().get_command().as_str()).collect();
+        assert_eq!(commands, vec!["pwd", "whoami"]);
+        assert_eq!(store.search(&["whoami"], SearchMode::Union).unwrap()[0].get_bookmark().get_collection(), "work");
+    }
+}
@@ -0,0 +1,386 @@
+use std::{
+    collections::HashSet,
+    env,
+    fmt::{Display, Formatter},
+    str::FromStr
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::bookmark::Bookmark;
+use super::journal::JournalEntry;
+use super::json::JsonStore;
+use super::metadata::CollectionMetadata;
+use super::sqlite::SqliteStore;
+use crate::error::{MemoireError, Result};
+
+
+static STORE_ENV_VAR: &str = "MEMOIRE_STORE";
+// Number of hex characters in a bookmark id
+const ID_LENGTH: usize = 8;
+const MAX_COLLECTION_NAME_LENGTH: usize = 64;
+
+
+#[derive(Hash, Eq, PartialEq, Clone)]
+pub struct SearchResult {
+    index: usize,
+    bookmark: Bookmark,
+}
+
+
+impl SearchResult {
+    pub fn new(index: usize, bookmark: Bookmark) -> SearchResult {
+        SearchResult {
+            index,
+            bookmark
+        }
+    }
+
+    pub fn get_bookmark(&self) -> &Bookmark {
+        &self.bookmark
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+}
+
+
+/// Deleted bookmark kept in the trash until it is restored or the trash is
+/// emptied
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TrashedBookmark {
+    bookmark: Bookmark,
+    // Position the bookmark had in its collection
+    index: usize,
+    deleted_at: DateTime<Utc>,
+}
+
+
+impl TrashedBookmark {
+    /// Returns the trashed form of the bookmark at index of its collection,
+    /// deleted now
+    pub fn new(index: usize, bookmark: Bookmark) -> TrashedBookmark {
+        TrashedBookmark {
+            bookmark,
+            index,
+            deleted_at: Utc::now()
+        }
+    }
+
+    /// Returns the trashed bookmark as it was when deleted
+    pub fn with_deleted_at(index: usize, bookmark: Bookmark, deleted_at: DateTime<Utc>) -> TrashedBookmark {
+        TrashedBookmark {
+            bookmark,
+            index,
+            deleted_at
+        }
+    }
+
+    pub fn get_bookmark(&self) -> &Bookmark {
+        &self.bookmark
+    }
+
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get_deleted_at(&self) -> &DateTime<Utc> {
+        &self.deleted_at
+    }
+}
+
+
+/// Collection skipped by the store because its file could not be loaded
+pub struct BrokenCollection {
+    name: String,
+    error: MemoireError,
+}
+
+
+impl BrokenCollection {
+    pub fn new(name: &str, error: MemoireError) -> BrokenCollection {
+        BrokenCollection {
+            name: name.to_owned(),
+            error
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_error(&self) -> &MemoireError {
+        &self.error
+    }
+}
+
+
+/// How the keywords of a search are combined
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchMode {
+    /// Bookmarks containing any of the keywords
+    Union,
+    /// Bookmarks containing every keyword
+    Intersection
+}
+
+
+/// Backend a store keeps its bookmarks in
+#[derive(Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Json,
+    Sqlite
+}
+
+
+impl FromStr for StoreKind {
+    type Err = MemoireError;
+
+    fn from_str(s: &str) -> Result<StoreKind> {
+        match s {
+            "json" => Ok(StoreKind::Json),
+            "sqlite" => Ok(StoreKind::Sqlite),
+            _ => Err(MemoireError::InvalidArgument(
+                format!("Unknown store \"{}\", expected json or sqlite", s)
+            ))
+        }
+    }
+}
+
+
+/// Bookmark attribute results can be sorted by
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Created,
+    Updated,
+    Used,
+    Count
+}
+
+
+impl SortKey {
+    /// Returns the key after self, None after the last one
+    pub fn next(self) -> Option<SortKey> {
+        match self {
+            SortKey::Created => Some(SortKey::Updated),
+            SortKey::Updated => Some(SortKey::Used),
+            SortKey::Used => Some(SortKey::Count),
+            SortKey::Count => None
+        }
+    }
+}
+
+
+impl Display for SortKey {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SortKey::Created => "created",
+            SortKey::Updated => "updated",
+            SortKey::Used => "used",
+            SortKey::Count => "count"
+        })
+    }
+}
+
+
+impl FromStr for SortKey {
+    type Err = MemoireError;
+
+    fn from_str(s: &str) -> Result<SortKey> {
+        match s {
+            "created" => Ok(SortKey::Created),
+            "updated" => Ok(SortKey::Updated),
+            "used" => Ok(SortKey::Used),
+            "count" => Ok(SortKey::Count),
+            _ => Err(MemoireError::InvalidArgument(format!("Unknown sort key {}", s)))
+        }
+    }
+}
+
+
+/// Storage backend holding the bookmarks of every collection
+///
+/// Bookmarks are identified by their id, the index of a SearchResult is the
+/// position of the bookmark within its collection. Adding, editing, deleting,
+/// restoring and moving a bookmark is recorded in the journal.
+pub trait Store {
+    /// Returns the names of all collections, sorted by name
+    fn collections(&self) -> Result<Vec<String>>;
+
+    /// Returns the collections that could not be loaded when the store was
+    /// opened, they are left untouched and hidden from the other methods
+    fn get_broken_collections(&self) -> &[BrokenCollection];
+
+    /// Returns why the trash could not be read when the store was opened,
+    /// None if it can be
+    fn get_trash_error(&self) -> Option<&MemoireError>;
+
+    /// Create an empty collection, fails if it already exists
+    ///
+    /// Collections are also created when a bookmark is added to one that does
+    /// not exist, the name of every new collection is validated.
+    fn create_collection(&self, name: &str) -> Result<()>;
+
+    /// Rename the collection from to to, updating the collection of every
+    /// bookmark in it
+    ///
+    /// Fails if to already exists, returns the number of bookmarks renamed
+    fn rename_collection(&self, from: &str, to: &str) -> Result<usize>;
+
+    /// Append every bookmark of the collection from to the end of the
+    /// collection into and remove from
+    ///
+    /// Returns the number of bookmarks moved
+    fn merge_collections(&self, from: &str, into: &str) -> Result<usize>;
+
+    /// Remove the collection name, moving its bookmarks to the trash
+    ///
+    /// Returns the number of bookmarks removed
+    fn delete_collection(&self, name: &str) -> Result<usize>;
+
+    /// Returns the metadata of collection, the defaults if it has none
+    fn get_metadata(&self, collection: &str) -> Result<CollectionMetadata>;
+
+    /// Replace the metadata of collection, fails if it does not exist
+    fn set_metadata(&self, collection: &str, metadata: &CollectionMetadata) -> Result<()>;
+
+    /// Returns true if the bookmarks of collection can not be changed, because
+    /// its metadata marks it read-only or, for stores keeping collections in
+    /// files, its file is not writable
+    ///
+    /// Changes to the bookmarks of a read-only collection, or to the
+    /// collection itself, fail with MemoireError::ReadOnly. Usage is not
+    /// recorded for its bookmarks.
+    fn is_read_only(&self, collection: &str) -> Result<bool>;
+
+    /// Returns every bookmark in collection, in order
+    fn list(&self, collection: &str) -> Result<Vec<SearchResult>>;
+
+    /// Returns every bookmark that contains any of the keywords, or all of
+    /// them depending on mode, in its command, annotation, collection or tags
+    fn search(&self, keywords: &[&str], mode: SearchMode) -> Result<Vec<SearchResult>>;
+
+    /// Returns the bookmark with id from any collection
+    fn find(&self, id: &str) -> Result<Option<SearchResult>>;
+
+    /// Insert bookmark at index of its collection, appends to the end of the
+    /// collection if index is None or out of range
+    ///
+    /// A new id is generated if the bookmark does not have one yet, returns
+    /// the id of the added bookmark
+    fn add(&self, bookmark: &Bookmark, index: Option<usize>) -> Result<String>;
+
+    /// Replace the bookmark with id, keeping its id, creation time and usage
+    ///
+    /// The position is kept if the collection is unchanged, otherwise the
+    /// bookmark is appended to its new collection.
+    fn update(&self, id: &str, bookmark: &Bookmark) -> Result<()>;
+
+    /// Record that the command of the bookmark with id was used
+    fn mark_used(&self, id: &str) -> Result<()>;
+
+    /// Move the bookmark with id to the trash
+    ///
+    /// Returns the removed bookmark along with the index it was at
+    fn delete(&self, id: &str) -> Result<SearchResult>;
+
+    /// Returns every bookmark in the trash, most recently deleted first
+    fn list_trash(&self) -> Result<Vec<TrashedBookmark>>;
+
+    /// Put the trashed bookmark with id back at its former index in its
+    /// collection, recreating the collection if it was deleted
+    ///
+    /// Returns the restored bookmark along with the index it ended up at
+    fn restore(&self, id: &str) -> Result<SearchResult>;
+
+    /// Permanently remove every bookmark in the trash
+    ///
+    /// Returns the number of bookmarks removed
+    fn empty_trash(&self) -> Result<usize>;
+
+    /// Move the bookmark with id to index to within its collection, to is
+    /// clamped to the last index
+    ///
+    /// Returns the index the bookmark ended up at
+    fn move_bookmark(&self, id: &str, to: usize) -> Result<usize>;
+
+    /// Returns every entry of the journal, oldest first
+    fn log(&self) -> Result<Vec<JournalEntry>>;
+}
+
+
+/// Open the store in dir_path, $MEMOIRE_STORE takes precedence over kind
+///
+/// # Arguments
+///
+/// * `dir_path` - A string slice that holds the collection directory
+/// * `kind` - The backend configured by the user
+/// * `default_collection` - The collection bookmarks without one are moved to
+pub fn open_store(dir_path: &str, kind: StoreKind, default_collection: &str) -> Result<Box<dyn Store>> {
+    let kind = match env::var(STORE_ENV_VAR) {
+        Ok(value) if !value.is_empty() => value.parse::<StoreKind>().map_err(
+            |_err| MemoireError::InvalidArgument(
+                format!("Unknown store \"{}\" in ${}, expected json or sqlite", value, STORE_ENV_VAR)
+            )
+        )?,
+        _ => kind
+    };
+    match kind {
+        StoreKind::Json => Ok(Box::new(JsonStore::open(dir_path, default_collection)?)),
+        StoreKind::Sqlite => Ok(Box::new(SqliteStore::open(dir_path, default_collection)?))
+    }
+}
+
+
+/// Make sure name can be used as a collection
+///
+/// Names are also file names, so they are limited to letters, digits,
+/// spaces, `-`, `_` and `.`, can not start with a dot and can not have
+/// leading or trailing spaces
+pub fn validate_collection_name(name: &str) -> Result<()> {
+    let reason = if name.is_empty() {
+        "can not be empty"
+    } else if name.chars().count() > MAX_COLLECTION_NAME_LENGTH {
+        "is too long"
+    } else if name.starts_with('.') {
+        "can not start with a dot"
+    } else if name.trim() != name {
+        "can not start or end with a space"
+    } else if !name.chars().all(|c| c.is_alphanumeric() || " -_.".contains(c)) {
+        "can only contain letters, digits, spaces, -, _ and ."
+    } else {
+        return Ok(());
+    };
+    Err(MemoireError::InvalidArgument(format!("Collection name \"{}\" {}", name, reason)))
+}
+
+
+/// Sort results with the most recent or most used bookmark first, bookmarks
+/// that were never stamped go last and ties keep their order
+pub fn sort_results(results: &mut [SearchResult], key: SortKey) {
+    results.sort_by(|a, b| {
+        let (a, b) = (a.get_bookmark(), b.get_bookmark());
+        match key {
+            SortKey::Created => b.get_created_at().cmp(&a.get_created_at()),
+            SortKey::Updated => b.get_updated_at().cmp(&a.get_updated_at()),
+            SortKey::Used => b.get_last_used_at().cmp(&a.get_last_used_at()),
+            SortKey::Count => b.get_use_count().cmp(&a.get_use_count())
+        }
+    });
+}
+
+
+/// Returns a short random id that is not in ids
+pub fn generate_id(ids: &HashSet<String>) -> String {
+    loop {
+        let id = Uuid::new_v4().to_simple().to_string()[..ID_LENGTH].to_owned();
+        if !ids.contains(&id) {
+            return id;
+        }
+    }
+}
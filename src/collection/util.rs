@@ -1,72 +1,233 @@
 use std::{
-    fs::{File, create_dir_all},
-    process,
-    io::prelude::*
+    env,
+    fs::{File, OpenOptions, copy, create_dir_all, remove_file, rename},
+    io::prelude::*,
+    path::{Path, PathBuf},
+    thread::sleep,
+    time::{Duration, Instant}
 };
 
+use chrono::{DateTime, Local, Utc};
+use fs2::FileExt;
 
+use super::migration::CollectionFile;
+use crate::error::{MemoireError, Result};
+
+
+// Collection directory used before the XDG data directory, kept if it exists
 static COLLECTION_DIR_NAME: &str = ".memoire";
+static DATA_DIR_NAME: &str = "memoire";
+static DIR_ENV_VAR: &str = "MEMOIRE_HOME";
+// Where bookmarks were saved before 0.1.2
+static LEGACY_HISTORY_NAME: &str = ".memoir_history.json";
 pub static DEFAULT_JSON_NAME: &str = "default";
+// Number of previous versions kept for each collection file
+static BACKUP_COUNT: usize = 3;
+pub static LOCK_FILE_NAME: &str = ".lock";
+// How long a writer waits for another process to release the lock
+pub const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+
+/// Exclusive advisory lock on a collection directory, released when dropped
+pub struct CollectionLock {
+    file: File
+}
 
 
-pub fn get_collection_dir_path() -> String {
-    get_full_path(COLLECTION_DIR_NAME)
+impl Drop for CollectionLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
 }
 
 
-pub fn get_json_path(collection_name: &str) -> String {
-    format!("{}/{}.json", &get_collection_dir_path(), collection_name)
+/// Returns the directory holding the collections
+///
+/// In order of precedence: dir_path given on the command line,
+/// $MEMOIRE_HOME, the directory in the configuration file, `~/.memoire` if it
+/// already exists, then `memoire` in the XDG data directory.
+///
+/// # Arguments
+///
+/// * `dir_path` - The directory given with --dir, if any
+/// * `config_dir_path` - The directory set in the configuration file, if any
+pub fn get_collection_dir_path(dir_path: Option<&str>, config_dir_path: Option<&str>) -> Result<String> {
+    if let Some(dir_path) = dir_path {
+        return Ok(dir_path.to_owned());
+    }
+    if let Some(dir_path) = env::var_os(DIR_ENV_VAR).filter(|dir_path| !dir_path.is_empty()) {
+        return path_to_string(PathBuf::from(dir_path));
+    }
+    if let Some(dir_path) = config_dir_path {
+        return Ok(dir_path.to_owned());
+    }
+    let legacy_dir_path = dirs::home_dir().map(|home_dir| home_dir.join(COLLECTION_DIR_NAME));
+    match legacy_dir_path {
+        Some(legacy_dir_path) if legacy_dir_path.is_dir() => path_to_string(legacy_dir_path),
+        _ => match dirs::data_dir() {
+            Some(data_dir) => path_to_string(data_dir.join(DATA_DIR_NAME)),
+            None => Err(MemoireError::NoDataDir)
+        }
+    }
 }
 
 
-fn get_full_path(relative_path: &str) -> String {
+pub fn get_legacy_history_path() -> Result<String> {
     match dirs::home_dir() {
-        Some(home_dir) => {
-            let mut full_path = home_dir.into_os_string();
-            full_path.push("/");
-            full_path.push(relative_path);
-            match full_path.to_str() {
-                Some(s) => {
-                    s.to_owned()
-                },
-                None => {
-                    panic!("Unable to convert OS string to &str")
+        Some(home_dir) => path_to_string(home_dir.join(LEGACY_HISTORY_NAME)),
+        None => Err(MemoireError::NoDataDir)
+    }
+}
+
+
+pub fn path_to_string(path: PathBuf) -> Result<String> {
+    path.into_os_string().into_string().map_err(
+        |path| MemoireError::InvalidArgument(format!("{:?} is not a valid unicode path", path))
+    )
+}
+
+
+pub fn create_collection_dir(path: &str) -> Result<()> {
+    create_dir_all(path).map_err(|err| MemoireError::Io(path.to_owned(), err))
+}
+
+
+/// Lock the collection directory for a read-modify-write of its files
+///
+/// Waits up to LOCK_TIMEOUT for other memoire processes to finish their
+/// changes. Readers do not need the lock since files are replaced atomically.
+pub fn lock_collection_dir(dir_path: &str) -> Result<CollectionLock> {
+    let lock_path = format!("{}/{}", dir_path, LOCK_FILE_NAME);
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_path)
+        .map_err(|err| MemoireError::Io(lock_path.to_owned(), err))?;
+    let start = Instant::now();
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(_) => return Ok(CollectionLock { file }),
+            Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+                if start.elapsed() >= LOCK_TIMEOUT {
+                    return Err(MemoireError::Locked(lock_path));
                 }
-            }
-        },
-        None => {
-            panic!("Unable to find $HOME")
+                sleep(LOCK_RETRY_INTERVAL);
+            },
+            Err(err) => return Err(MemoireError::Io(lock_path, err))
         }
     }
 }
 
 
-pub fn create_collection_dir(path: &str) {
-    match create_dir_all(path) {
-        Ok(_) => {},
-        Err(_err) => {
-            println!("Unable to create directory: {}", path);
-            process::exit(0);
+/// Atomically replace json_path with content
+///
+/// The content is written to a temporary file, synced to disk and then
+/// renamed over json_path, so a crash leaves either the old or the new
+/// collection in place. The previous version is kept as a rotating backup.
+/// Nothing is written if content is not a valid collection file.
+///
+/// # Arguments
+///
+/// * `json_path` - A string slice that holds the path of the collection file
+/// * `content` - The new content, defaults to an empty collection
+pub fn write_to_json(json_path: &str, content: Option<&str>) -> Result<()> {
+    let empty = serde_json::to_string(&CollectionFile::new(&[])).map_err(
+        |err| MemoireError::Json(json_path.to_owned(), err)
+    )?;
+    let content = content.unwrap_or(&empty);
+    serde_json::from_str::<CollectionFile>(content).map_err(
+        |err| MemoireError::Json(json_path.to_owned(), err)
+    )?;
+
+    let tmp_path = format!("{}.tmp", json_path);
+    if let Err(err) = write_and_sync(&tmp_path, content) {
+        let _ = remove_file(&tmp_path);
+        return Err(err);
+    }
+    if Path::new(json_path).exists() {
+        rotate_backups(json_path)?;
+    }
+    rename(&tmp_path, json_path).map_err(
+        |err| MemoireError::Io(json_path.to_owned(), err)
+    )?;
+    sync_parent_dir(json_path);
+    Ok(())
+}
+
+
+/// Atomically replace path with content, without keeping a backup
+pub fn write_file(path: &str, content: &str) -> Result<()> {
+    let tmp_path = format!("{}.tmp", path);
+    if let Err(err) = write_and_sync(&tmp_path, content) {
+        let _ = remove_file(&tmp_path);
+        return Err(err);
+    }
+    rename(&tmp_path, path).map_err(|err| MemoireError::Io(path.to_owned(), err))?;
+    sync_parent_dir(path);
+    Ok(())
+}
+
+
+/// Remove json_path, keeping its last version as the newest backup
+pub fn remove_json(json_path: &str) -> Result<()> {
+    rotate_backups(json_path)?;
+    remove_file(json_path).map_err(
+        |err| MemoireError::Io(json_path.to_owned(), err)
+    )?;
+    sync_parent_dir(json_path);
+    Ok(())
+}
+
+
+/// Returns the path of the nth most recent backup of json_path
+fn get_backup_path(json_path: &str, nth: usize) -> String {
+    format!("{}.{}.bak", json_path, nth)
+}
+
+
+fn write_and_sync(path: &str, content: &str) -> Result<()> {
+    let mut file = File::create(path).map_err(
+        |err| MemoireError::Io(path.to_owned(), err)
+    )?;
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.sync_all())
+        .map_err(|err| MemoireError::Io(path.to_owned(), err))
+}
+
+
+/// Shift every backup of json_path one slot older and copy the current
+/// file into the newest slot
+fn rotate_backups(json_path: &str) -> Result<()> {
+    for nth in (1..BACKUP_COUNT).rev() {
+        let older = get_backup_path(json_path, nth);
+        if Path::new(&older).exists() {
+            let oldest = get_backup_path(json_path, nth + 1);
+            rename(&older, &oldest).map_err(|err| MemoireError::Io(oldest, err))?;
         }
     }
+    let newest = get_backup_path(json_path, 1);
+    copy(json_path, &newest).map_err(|err| MemoireError::Io(newest, err))?;
+    Ok(())
 }
 
 
-pub fn write_to_json(json_path: &str, content: Option<&str>) {
-    match File::create(json_path) {
-        Ok(mut file) => {
-            // Default content is empty list
-            match file.write_all(content.unwrap_or("[]").as_bytes()) {
-                Ok(_) => {},
-                Err(_err) => {
-                    println!("Unable to write to file: {}", json_path);
-                    process::exit(0);
-                }
-            }
-        },
-        Err(_err) => {
-            println!("Unable to create file: {}", json_path);
-            process::exit(0);
+/// Make sure the rename of a file in the directory is persisted
+fn sync_parent_dir(path: &str) {
+    if let Some(parent) = Path::new(path).parent() {
+        // Directories can not be opened for syncing on every platform
+        if let Ok(dir) = File::open(parent) {
+            let _ = dir.sync_all();
         }
     }
-}
\ No newline at end of file
+}
+
+
+/// Returns time in the local timezone, or "-" if it was never set
+pub fn format_time(time: Option<&DateTime<Utc>>) -> String {
+    match time {
+        Some(time) => time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(),
+        None => "-".to_owned()
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Deserialize;
+use serde_json::{value::RawValue, Value};
+
+use super::bookmark::Bookmark;
+
+
+/// A problem found in a collection file, at the given line and column
+#[derive(Debug)]
+pub struct Diagnostic {
+    line: usize,
+    column: usize,
+    message: String,
+}
+
+
+/// Collection file with the bookmarks left unparsed, to locate them
+#[derive(Deserialize)]
+struct RawCollectionFile<'a> {
+    // Only checked, the version was read before
+    #[serde(rename = "version")]
+    _version: u64,
+    #[serde(borrow)]
+    bookmarks: Vec<&'a RawValue>,
+}
+
+
+impl Diagnostic {
+    pub fn new(line: usize, column: usize, message: &str) -> Diagnostic {
+        Diagnostic {
+            line,
+            column,
+            message: message.to_owned()
+        }
+    }
+
+    /// Returns the diagnostic of a json error, without the position repeated
+    /// in the message
+    pub fn from_json_error(err: &serde_json::Error) -> Diagnostic {
+        let message = err.to_string();
+        let position = format!(" at line {} column {}", err.line(), err.column());
+        Diagnostic::new(
+            err.line(),
+            err.column(),
+            message.strip_suffix(&position).unwrap_or(&message)
+        )
+    }
+}
+
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+
+/// Check every bookmark of a collection file against the schema of its
+/// format version
+///
+/// Returns one diagnostic per invalid bookmark, located at the start of the
+/// bookmark, or a single one if the file itself is malformed.
+///
+/// # Arguments
+///
+/// * `content` - A string slice that holds the content of the file
+/// * `version` - The format version of the file
+/// * `collection` - The collection of the file
+/// * `check_collection` - Whether the collection field of every bookmark must be collection
+pub fn validate_collection(content: &str, version: u64, collection: &str, check_collection: bool) -> Vec<Diagnostic> {
+    let entries = match read_entries(content, version, collection) {
+        Ok(entries) => entries,
+        Err(diagnostic) => return vec![diagnostic]
+    };
+    entries.iter().enumerate().filter_map(|(index, entry)| match &entry.parsed {
+        Ok(bookmark) => if check_collection && bookmark.get_collection() != collection {
+            Some(entry.diagnose(&format!(
+                "bookmark {}: collection \"{}\" does not match the file name \"{}\"",
+                index, bookmark.get_collection(), collection
+            )))
+        } else {
+            None
+        },
+        Err(err) => Some(entry.diagnose(&format!("bookmark {}: {}", index, err)))
+    }).collect()
+}
+
+
+/// Keep the bookmarks of a collection file that match the schema of its
+/// format version, moving the ones of another collection to collection
+///
+/// Returns the bookmarks kept along with a diagnostic for every bookmark
+/// dropped or moved, fails if the file itself is malformed.
+pub fn salvage_collection(
+    content: &str,
+    version: u64,
+    collection: &str
+) -> std::result::Result<(Vec<Bookmark>, Vec<Diagnostic>), Diagnostic> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+    for (index, entry) in read_entries(content, version, collection)?.into_iter().enumerate() {
+        match &entry.parsed {
+            Ok(bookmark) => {
+                let mut bookmark = bookmark.to_owned();
+                if bookmark.get_collection() != collection {
+                    diagnostics.push(entry.diagnose(&format!(
+                        "bookmark {}: moved from collection \"{}\" to \"{}\"",
+                        index, bookmark.get_collection(), collection
+                    )));
+                    bookmark.set_collection(collection);
+                }
+                bookmarks.push(bookmark);
+            },
+            Err(err) => diagnostics.push(entry.diagnose(&format!("bookmark {}: dropped, {}", index, err)))
+        }
+    }
+    Ok((bookmarks, diagnostics))
+}
+
+
+/// A bookmark of a collection file, parsed on its own
+struct Entry {
+    line: usize,
+    column: usize,
+    parsed: std::result::Result<Bookmark, String>,
+}
+
+
+impl Entry {
+    fn diagnose(&self, message: &str) -> Diagnostic {
+        Diagnostic::new(self.line, self.column, message)
+    }
+}
+
+
+/// Parse every bookmark of a collection file separately, fails if the file
+/// is not a list of bookmarks in the layout of its version
+fn read_entries(content: &str, version: u64, collection: &str) -> std::result::Result<Vec<Entry>, Diagnostic> {
+    // Versions before 2 are bare lists of bookmarks
+    let raw_bookmarks = match version {
+        0 | 1 => serde_json::from_str::<Vec<&RawValue>>(content),
+        _ => serde_json::from_str::<RawCollectionFile>(content).map(|file| file.bookmarks)
+    };
+    let raw_bookmarks = raw_bookmarks.map_err(|err| Diagnostic::from_json_error(&err))?;
+    Ok(raw_bookmarks.iter().map(|raw_bookmark| {
+        let (line, column) = get_position(content, raw_bookmark.get());
+        let parsed = serde_json::from_str::<Value>(raw_bookmark.get()).and_then(|mut value| {
+            // Bookmarks did not have a collection before version 1
+            if let (0, Value::Object(fields)) = (version, &mut value) {
+                fields.entry("collection").or_insert_with(|| Value::from(collection));
+            }
+            serde_json::from_value::<Bookmark>(value)
+        });
+        Entry {
+            line,
+            column,
+            parsed: parsed.map_err(|err| err.to_string())
+        }
+    }).collect())
+}
+
+
+/// Returns the line and column where slice, a part of content, starts
+fn get_position(content: &str, slice: &str) -> (usize, usize) {
+    let offset = (slice.as_ptr() as usize).saturating_sub(content.as_ptr() as usize).min(content.len());
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
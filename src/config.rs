@@ -0,0 +1,316 @@
+use std::{env, ffi::OsString, fs::read_to_string, path::{Path, PathBuf}};
+
+use serde::{de, Deserialize, Deserializer};
+use tui::style::Color;
+
+use crate::collection::{
+    store::{SearchMode, StoreKind, validate_collection_name},
+    util::DEFAULT_JSON_NAME
+};
+use crate::error::{MemoireError, Result};
+
+
+static CONFIG_DIR_NAME: &str = "memoire";
+static CONFIG_FILE_NAME: &str = "config.toml";
+static CONFIG_HOME_ENV_VAR: &str = "XDG_CONFIG_HOME";
+// Configuration directory in the home directory when $XDG_CONFIG_HOME is not set
+static DEFAULT_CONFIG_HOME_NAME: &str = ".config";
+// The percent columns leave room for the id column and the spacing between
+// columns on an 80 column terminal
+const MAX_COLUMNS_TOTAL: u32 = 85;
+
+
+/// User configuration read from `~/.config/memoire/config.toml`, every key
+/// is optional
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Collection of bookmarks added without one
+    default_collection: String,
+    // Directory holding the collections, after --dir and $MEMOIRE_HOME
+    dir: Option<String>,
+    // Backend of the collections, after $MEMOIRE_STORE
+    store: StoreKind,
+    // Whether searches return bookmarks matching any or all keywords
+    search_mode: SearchMode,
+    // Whether the TUI quits after copying a command
+    copy_then_quit: bool,
+    columns: ColumnWidths,
+    colors: Colors,
+}
+
+
+/// Width of the result table columns in percent of the table
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColumnWidths {
+    command: u16,
+    annotation: u16,
+    tags: u16,
+    collection: u16,
+}
+
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default, deny_unknown_fields)]
+pub struct Colors {
+    // Column names of the result table
+    #[serde(deserialize_with = "deserialize_color")]
+    header: Color,
+    // Rows of the result table and the other lists
+    #[serde(deserialize_with = "deserialize_color")]
+    text: Color,
+    // Selected row and focused input
+    #[serde(deserialize_with = "deserialize_color")]
+    selected: Color,
+}
+
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            default_collection: DEFAULT_JSON_NAME.to_owned(),
+            dir: None,
+            store: StoreKind::Json,
+            search_mode: SearchMode::Union,
+            copy_then_quit: true,
+            columns: ColumnWidths::default(),
+            colors: Colors::default(),
+        }
+    }
+}
+
+
+impl Default for ColumnWidths {
+    fn default() -> ColumnWidths {
+        ColumnWidths {
+            command: 30,
+            annotation: 22,
+            tags: 17,
+            collection: 16,
+        }
+    }
+}
+
+
+impl Default for Colors {
+    fn default() -> Colors {
+        Colors {
+            header: Color::LightGreen,
+            text: Color::White,
+            selected: Color::LightYellow,
+        }
+    }
+}
+
+
+impl Config {
+    /// Load the configuration file, every key missing from it or a missing
+    /// file keeps its default value
+    ///
+    /// Fails with the offending key if the file can not be parsed
+    pub fn load() -> Result<Config> {
+        match get_config_home(env::var_os(CONFIG_HOME_ENV_VAR), dirs::home_dir()) {
+            Some(config_dir) => {
+                let config_path = config_dir.join(CONFIG_DIR_NAME).join(CONFIG_FILE_NAME);
+                Config::load_from(&config_path)
+            },
+            None => Ok(Config::default())
+        }
+    }
+
+    fn load_from(config_path: &Path) -> Result<Config> {
+        if !config_path.exists() {
+            return Ok(Config::default());
+        }
+        let path = config_path.to_string_lossy().into_owned();
+        let content = read_to_string(config_path).map_err(
+            |err| MemoireError::Io(path.to_owned(), err)
+        )?;
+        let config: Config = toml::from_str(&content).map_err(
+            |err| MemoireError::Config(path.to_owned(), err.to_string())
+        )?;
+        validate_collection_name(&config.default_collection).map_err(
+            |err| MemoireError::Config(path.to_owned(), format!("default_collection: {}", err))
+        )?;
+        if config.columns.total() > MAX_COLUMNS_TOTAL {
+            return Err(MemoireError::Config(
+                path,
+                format!(
+                    "columns add up to {} percent, at most {} is allowed to leave room for the id column",
+                    config.columns.total(), MAX_COLUMNS_TOTAL
+                )
+            ));
+        }
+        Ok(config)
+    }
+
+    pub fn get_default_collection(&self) -> &str {
+        &self.default_collection
+    }
+
+    pub fn get_dir(&self) -> Option<&str> {
+        self.dir.as_deref()
+    }
+
+    pub fn get_store(&self) -> StoreKind {
+        self.store
+    }
+
+    pub fn get_search_mode(&self) -> SearchMode {
+        self.search_mode
+    }
+
+    pub fn get_copy_then_quit(&self) -> bool {
+        self.copy_then_quit
+    }
+
+    pub fn get_columns(&self) -> &ColumnWidths {
+        &self.columns
+    }
+
+    pub fn get_colors(&self) -> &Colors {
+        &self.colors
+    }
+}
+
+
+/// Returns $XDG_CONFIG_HOME, or `.config` in the home directory if it is not
+/// set to an absolute path, on every platform
+///
+/// # Arguments
+///
+/// * `config_home` - The value of $XDG_CONFIG_HOME, if set
+/// * `home_dir` - The home directory, if known
+fn get_config_home(config_home: Option<OsString>, home_dir: Option<PathBuf>) -> Option<PathBuf> {
+    match config_home.map(PathBuf::from) {
+        Some(config_home) if config_home.is_absolute() => Some(config_home),
+        _ => home_dir.map(|home_dir| home_dir.join(DEFAULT_CONFIG_HOME_NAME))
+    }
+}
+
+
+impl ColumnWidths {
+    pub fn get_command(&self) -> u16 {
+        self.command
+    }
+
+    pub fn get_annotation(&self) -> u16 {
+        self.annotation
+    }
+
+    pub fn get_tags(&self) -> u16 {
+        self.tags
+    }
+
+    pub fn get_collection(&self) -> u16 {
+        self.collection
+    }
+
+    fn total(&self) -> u32 {
+        [self.command, self.annotation, self.tags, self.collection].iter().map(|&width| u32::from(width)).sum()
+    }
+}
+
+
+impl Colors {
+    pub fn get_header(&self) -> Color {
+        self.header
+    }
+
+    pub fn get_text(&self) -> Color {
+        self.text
+    }
+
+    pub fn get_selected(&self) -> Color {
+        self.selected
+    }
+}
+
+
+/// Deserialize a color from its name, like `lightgreen`, or a `#rrggbb`
+/// hex code
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Color, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    parse_color(&name).ok_or_else(
+        || de::Error::custom(format!("unknown color \"{}\", expected a name like lightgreen or #rrggbb", name))
+    )
+}
+
+
+/// Returns the color named name, like `lightgreen`, or given as a `#rrggbb`
+/// hex code
+pub fn parse_color(name: &str) -> Option<Color> {
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    match name.to_lowercase().replace(['_', '-'], "").as_str() {
+        "black" => Some(Color::Black),
+        "red" => Some(Color::Red),
+        "green" => Some(Color::Green),
+        "yellow" => Some(Color::Yellow),
+        "blue" => Some(Color::Blue),
+        "magenta" => Some(Color::Magenta),
+        "cyan" => Some(Color::Cyan),
+        "gray" => Some(Color::Gray),
+        "darkgray" => Some(Color::DarkGray),
+        "lightred" => Some(Color::LightRed),
+        "lightgreen" => Some(Color::LightGreen),
+        "lightyellow" => Some(Color::LightYellow),
+        "lightblue" => Some(Color::LightBlue),
+        "lightmagenta" => Some(Color::LightMagenta),
+        "lightcyan" => Some(Color::LightCyan),
+        "white" => Some(Color::White),
+        _ => None
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+
+    #[test]
+    fn default_columns_leave_room_for_id_column() {
+        assert!(ColumnWidths::default().total() <= MAX_COLUMNS_TOTAL);
+    }
+
+
+    #[test]
+    fn columns_filling_the_table_are_rejected() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join(CONFIG_FILE_NAME);
+        write(&config_path, "[columns]\ncommand = 40\nannotation = 20\ntags = 20\ncollection = 20\n").unwrap();
+        assert!(matches!(Config::load_from(&config_path), Err(MemoireError::Config(_, _))));
+    }
+
+
+    #[test]
+    fn config_home_prefers_absolute_xdg_config_home() {
+        assert_eq!(
+            get_config_home(Some(OsString::from("/etc/xdg")), Some(PathBuf::from("/home/me"))),
+            Some(PathBuf::from("/etc/xdg"))
+        );
+    }
+
+
+    #[test]
+    fn config_home_falls_back_to_dot_config() {
+        for config_home in [None, Some(OsString::from("")), Some(OsString::from("relative"))] {
+            assert_eq!(
+                get_config_home(config_home, Some(PathBuf::from("/home/me"))),
+                Some(PathBuf::from("/home/me/.config"))
+            );
+        }
+        assert_eq!(get_config_home(None, None), None);
+    }
+}
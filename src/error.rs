@@ -0,0 +1,124 @@
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    io,
+    sync::mpsc
+};
+
+use crate::collection::validation::Diagnostic;
+
+
+pub type Result<T> = std::result::Result<T, MemoireError>;
+
+
+#[derive(Debug)]
+pub enum MemoireError {
+    /// Failed to read or write the file or directory at the given path
+    Io(String, io::Error),
+    /// The file at the given path does not hold a valid list of bookmarks
+    Json(String, serde_json::Error),
+    /// The collection file at the given path does not match the schema
+    InvalidCollection(String, Vec<Diagnostic>),
+    /// The file at the given path was written by a newer format version
+    UnsupportedVersion(String, u64),
+    /// Failed to query the sqlite store
+    Sqlite(rusqlite::Error),
+    /// Failed to access the system clipboard
+    Clipboard(arboard::Error),
+    /// Failed to set up or draw to the terminal
+    Terminal(io::Error),
+    /// The key event channel was closed
+    Event(mpsc::RecvError),
+    /// An argument given by the user could not be used
+    InvalidArgument(String),
+    /// Another process kept the lock file at the given path for too long
+    Locked(String),
+    /// Neither a collection directory nor a home directory could be found
+    NoDataDir,
+    /// The configuration file at the given path is invalid, with the reason
+    Config(String, String),
+    /// The bookmarks of the given collection can not be changed
+    ReadOnly(String),
+    /// The file at the given path could not be imported, with the reason
+    Import(String, String),
+    /// The trash file at the given path does not hold a valid list of bookmarks
+    BrokenTrash(String, serde_json::Error),
+}
+
+
+impl Display for MemoireError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        match self {
+            MemoireError::Io(path, err) => write!(f, "Unable to access {}: {}", path, err),
+            MemoireError::Json(path, err) => write!(f, "File {} contains invalid json format: {}", path, err),
+            MemoireError::InvalidCollection(path, diagnostics) => {
+                write!(f, "File {} is not a valid collection:", path)?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n    {}", diagnostic)?;
+                }
+                Ok(())
+            },
+            MemoireError::UnsupportedVersion(path, version) => write!(
+                f, "File {} uses format version {} from a newer memoire, please upgrade", path, version
+            ),
+            MemoireError::Sqlite(err) => write!(f, "Database error: {}", err),
+            MemoireError::Clipboard(err) => write!(f, "Unable to access clipboard: {}", err),
+            MemoireError::Terminal(err) => write!(f, "Terminal error: {}", err),
+            MemoireError::Event(err) => write!(f, "Unable to read key events: {}", err),
+            MemoireError::InvalidArgument(msg) => write!(f, "Invalid argument: {}", msg),
+            MemoireError::Locked(path) => write!(f, "Collections are locked by another memoire process ({})", path),
+            MemoireError::NoDataDir => write!(
+                f, "Unable to find a directory for collections, set $MEMOIRE_HOME or use --dir"
+            ),
+            MemoireError::Config(path, msg) => write!(f, "Invalid configuration in {}: {}", path, msg),
+            MemoireError::ReadOnly(collection) => write!(f, "Collection {} is read-only", collection),
+            MemoireError::Import(path, msg) => write!(f, "Unable to import {}: {}", path, msg),
+            MemoireError::BrokenTrash(path, err) => write!(
+                f, "Trash {} is damaged ({}), run memoire doctor --fix to set it aside", path, err
+            ),
+        }
+    }
+}
+
+
+impl Error for MemoireError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MemoireError::Io(_, err) => Some(err),
+            MemoireError::Json(_, err) | MemoireError::BrokenTrash(_, err) => Some(err),
+            MemoireError::Sqlite(err) => Some(err),
+            MemoireError::Clipboard(err) => Some(err),
+            MemoireError::Terminal(err) => Some(err),
+            MemoireError::Event(err) => Some(err),
+            MemoireError::InvalidCollection(_, _)
+                | MemoireError::UnsupportedVersion(_, _)
+                | MemoireError::InvalidArgument(_)
+                | MemoireError::Locked(_)
+                | MemoireError::NoDataDir
+                | MemoireError::Config(_, _)
+                | MemoireError::ReadOnly(_)
+                | MemoireError::Import(_, _) => None,
+        }
+    }
+}
+
+
+impl From<rusqlite::Error> for MemoireError {
+    fn from(err: rusqlite::Error) -> MemoireError {
+        MemoireError::Sqlite(err)
+    }
+}
+
+
+impl From<arboard::Error> for MemoireError {
+    fn from(err: arboard::Error) -> MemoireError {
+        MemoireError::Clipboard(err)
+    }
+}
+
+
+impl From<mpsc::RecvError> for MemoireError {
+    fn from(err: mpsc::RecvError) -> MemoireError {
+        MemoireError::Event(err)
+    }
+}
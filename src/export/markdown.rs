@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::collection::{bookmark::Bookmark, store::Store};
+use crate::error::{MemoireError, Result};
+
+
+// Language of the fenced code blocks holding commands
+static CODE_LANGUAGE: &str = "sh";
+// Shortest fence markdown accepts for a code block
+const MIN_FENCE_LENGTH: usize = 3;
+
+
+/// Returns a markdown cheatsheet of the bookmarks, with a section per
+/// collection and a subsection per tag
+///
+/// Collections rank by priority like in the TUI. A bookmark is listed under
+/// its first tag, or the first of the given tags it has, bookmarks without
+/// tags come before the tag subsections.
+///
+/// # Arguments
+///
+/// * `store` - The store holding the bookmarks
+/// * `collections` - The collections to export, all if empty
+/// * `tags` - Only export bookmarks having one of these tags, all if empty
+pub fn render_cheatsheet(store: &dyn Store, collections: &[&str], tags: &[&str]) -> Result<String> {
+    let mut names = store.collections()?;
+    if !collections.is_empty() {
+        for collection in collections {
+            if !names.iter().any(|name| name == collection) {
+                return Err(MemoireError::InvalidArgument(format!("No collection named {}", collection)));
+            }
+        }
+        names.retain(|name| collections.contains(&name.as_str()));
+    }
+    let mut sections: Vec<(i64, String)> = Vec::new();
+    for name in names {
+        let results = store.list(&name)?;
+        let bookmarks: Vec<&Bookmark> = results.iter().map(|result| result.get_bookmark()).filter(
+            |bookmark| tags.is_empty() || bookmark.get_tags().iter().any(|tag| tags.contains(&tag.as_str()))
+        ).collect();
+        if bookmarks.is_empty() {
+            continue;
+        }
+        let metadata = store.get_metadata(&name)?;
+        let mut blocks: Vec<String> = vec![format!("# {}", metadata.get_label(&name))];
+        if !metadata.get_description().is_empty() {
+            blocks.push(metadata.get_description().to_owned());
+        }
+        if !metadata.get_owner().is_empty() {
+            blocks.push(format!("Owner: {}", metadata.get_owner()));
+        }
+        let mut untagged: Vec<&Bookmark> = Vec::new();
+        let mut by_tag: BTreeMap<&str, Vec<&Bookmark>> = BTreeMap::new();
+        for bookmark in bookmarks {
+            match get_section_tag(bookmark, tags) {
+                Some(tag) => by_tag.entry(tag).or_default().push(bookmark),
+                None => untagged.push(bookmark)
+            }
+        }
+        blocks.extend(untagged.into_iter().map(render_bookmark));
+        for (tag, bookmarks) in by_tag {
+            blocks.push(format!("## {}", tag));
+            blocks.extend(bookmarks.into_iter().map(render_bookmark));
+        }
+        sections.push((metadata.get_priority(), blocks.join("\n\n")));
+    }
+    // Stable, collections of the same priority stay in name order
+    sections.sort_by(|(a, _), (b, _)| b.cmp(a));
+    let sections: Vec<String> = sections.into_iter().map(|(_, section)| section).collect();
+    Ok(if sections.is_empty() { String::new() } else { format!("{}\n", sections.join("\n\n")) })
+}
+
+
+/// Returns the tag bookmark is listed under, the first of tags it has or its
+/// first tag if tags is empty
+fn get_section_tag<'a>(bookmark: &'a Bookmark, tags: &[&str]) -> Option<&'a str> {
+    bookmark.get_tags().iter().map(|tag| tag.as_str()).find(|tag| tags.is_empty() || tags.contains(tag))
+}
+
+
+/// Returns the annotation of bookmark as a paragraph followed by its command
+/// in a fenced code block
+fn render_bookmark(bookmark: &Bookmark) -> String {
+    let command = bookmark.get_command();
+    // The fence must be longer than any backtick run of the command
+    let mut longest_run = 0;
+    let mut run = 0;
+    for c in command.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    let fence = "`".repeat(MIN_FENCE_LENGTH.max(longest_run + 1));
+    let code = format!("{0}{1}\n{2}\n{0}", fence, CODE_LANGUAGE, command);
+    let annotation = bookmark.get_annotation().trim();
+    if annotation.is_empty() {
+        code
+    } else {
+        format!("{}\n\n{}", annotation, code)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::collection::{json::JsonStore, metadata::CollectionMetadata};
+
+
+    #[test]
+    fn cheatsheet_groups_by_collection_and_tag() {
+        let dir = tempdir().unwrap();
+        let store = JsonStore::open(dir.path().to_str().unwrap(), "default").unwrap();
+        store.add(&Bookmark::new("ls -la", "", &Vec::new(), "shell"), None).unwrap();
+        store.add(&Bookmark::new("echo `date`", "Print the date", &vec!["time".to_owned()], "shell"), None).unwrap();
+        store.add(&Bookmark::new("git status", "", &vec!["git".to_owned()], "team"), None).unwrap();
+        let mut metadata = CollectionMetadata::default();
+        metadata.set_description("Team commands");
+        metadata.set_priority(1);
+        store.set_metadata("team", &metadata).unwrap();
+
+        assert_eq!(render_cheatsheet(&store, &[], &[]).unwrap(), "\
+# team
+
+Team commands
+
+## git
+
+```sh
+git status
+```
+
+# shell
+
+```sh
+ls -la
+```
+
+## time
+
+Print the date
+
+```sh
+echo `date`
+```
+");
+    }
+
+
+    #[test]
+    fn cheatsheet_filters_by_tag_and_collection() {
+        let dir = tempdir().unwrap();
+        let store = JsonStore::open(dir.path().to_str().unwrap(), "default").unwrap();
+        store.add(&Bookmark::new("ls", "", &vec!["fs".to_owned(), "shell".to_owned()], "shell"), None).unwrap();
+        store.add(&Bookmark::new("pwd", "", &Vec::new(), "shell"), None).unwrap();
+        assert_eq!(render_cheatsheet(&store, &["shell"], &["shell"]).unwrap(), "# shell\n\n## shell\n\n```sh\nls\n```\n");
+        assert_eq!(render_cheatsheet(&store, &[], &["none"]).unwrap(), "");
+        assert!(matches!(render_cheatsheet(&store, &["missing"], &[]), Err(MemoireError::InvalidArgument(_))));
+    }
+
+
+    #[test]
+    fn fence_is_longer_than_backticks_in_command() {
+        let bookmark = Bookmark::new("echo ```", "", &Vec::new(), "shell");
+        assert_eq!(render_bookmark(&bookmark), "````sh\necho ```\n````");
+    }
+}
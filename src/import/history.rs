@@ -0,0 +1,289 @@
+use std::{
+    env,
+    fmt::{Display, Formatter},
+    fs::read,
+    path::Path,
+    str::FromStr
+};
+
+use crate::collection::util::path_to_string;
+use crate::error::{MemoireError, Result};
+
+
+// Zsh escapes bytes of multibyte characters that it uses internally
+const ZSH_META: u8 = 0x83;
+
+
+/// Shell whose history file can be imported
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish
+}
+
+
+impl Display for Shell {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish"
+        })
+    }
+}
+
+
+impl FromStr for Shell {
+    type Err = MemoireError;
+
+    fn from_str(s: &str) -> Result<Shell> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(MemoireError::InvalidArgument(format!("Unknown shell {}, expected bash, zsh or fish", s)))
+        }
+    }
+}
+
+
+impl Shell {
+    /// Returns the shell that wrote the history file at path, guessed from
+    /// its name, or the login shell in $SHELL, bash if neither tells
+    pub fn detect(history_path: Option<&str>) -> Shell {
+        let name = match history_path {
+            Some(history_path) => history_path.to_owned(),
+            None => env::var("SHELL").unwrap_or_default()
+        };
+        let name = Path::new(&name).file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if name.contains("zsh") {
+            Shell::Zsh
+        } else if name.contains("fish") {
+            Shell::Fish
+        } else {
+            Shell::Bash
+        }
+    }
+
+    /// Returns the path the shell saves its history to by default
+    pub fn get_history_path(&self) -> Result<String> {
+        let path = match self {
+            Shell::Bash => dirs::home_dir().map(|home_dir| home_dir.join(".bash_history")),
+            Shell::Zsh => dirs::home_dir().map(|home_dir| home_dir.join(".zsh_history")),
+            Shell::Fish => dirs::data_dir().map(|data_dir| data_dir.join("fish").join("fish_history"))
+        };
+        match path {
+            Some(path) => path_to_string(path),
+            None => Err(MemoireError::NoDataDir)
+        }
+    }
+}
+
+
+/// Returns the distinct commands of the history file at history_path, most
+/// recently run first
+///
+/// # Arguments
+///
+/// * `history_path` - The path of the history file
+/// * `shell` - The shell that wrote the history file
+pub fn read_history(history_path: &str, shell: Shell) -> Result<Vec<String>> {
+    let content = read(history_path).map_err(|err| MemoireError::Io(history_path.to_owned(), err))?;
+    Ok(parse_history(&content, shell))
+}
+
+
+/// Returns the distinct commands of a history file, most recently run first
+///
+/// The content is bytes as zsh escapes multibyte characters, invalid utf-8
+/// is replaced.
+fn parse_history(content: &[u8], shell: Shell) -> Vec<String> {
+    let commands = match shell {
+        Shell::Bash => parse_bash(&String::from_utf8_lossy(content)),
+        Shell::Zsh => parse_zsh(&String::from_utf8_lossy(&unmetafy(content))),
+        Shell::Fish => parse_fish(&String::from_utf8_lossy(content))
+    };
+    let mut distinct: Vec<String> = Vec::new();
+    for command in commands.into_iter().rev() {
+        let command = command.trim();
+        if !command.is_empty() && !distinct.iter().any(|other| other == command) {
+            distinct.push(command.to_owned());
+        }
+    }
+    distinct
+}
+
+
+/// Returns the commands of a bash history, oldest first
+///
+/// With HISTTIMEFORMAT set, every command is preceded by a `#<timestamp>`
+/// line and may span several lines. Otherwise every line is a command.
+fn parse_bash(content: &str) -> Vec<String> {
+    let is_timestamp = |line: &str| line.len() > 1
+        && line.starts_with('#')
+        && line[1..].chars().all(|c| c.is_ascii_digit());
+    if !content.lines().any(is_timestamp) {
+        return content.lines().map(|line| line.to_owned()).collect();
+    }
+    let mut commands: Vec<String> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        if is_timestamp(line) {
+            if !lines.is_empty() {
+                commands.push(lines.join("\n"));
+                lines.clear();
+            }
+        } else {
+            lines.push(line);
+        }
+    }
+    if !lines.is_empty() {
+        commands.push(lines.join("\n"));
+    }
+    commands
+}
+
+
+/// Returns the commands of a zsh history, oldest first
+///
+/// Extended history lines look like `: <start>:<elapsed>;<command>`, plain
+/// lines are commands. Lines ending with a backslash continue on the next.
+fn parse_zsh(content: &str) -> Vec<String> {
+    let mut commands: Vec<String> = Vec::new();
+    let mut lines: Vec<&str> = Vec::new();
+    for line in content.lines() {
+        let line = if lines.is_empty() { strip_zsh_extended_prefix(line) } else { line };
+        match line.strip_suffix('\\') {
+            Some(line) => lines.push(line),
+            None => {
+                lines.push(line);
+                commands.push(lines.join("\n"));
+                lines.clear();
+            }
+        }
+    }
+    if !lines.is_empty() {
+        commands.push(lines.join("\n"));
+    }
+    commands
+}
+
+
+/// Returns line without the `: <start>:<elapsed>;` prefix of extended history
+fn strip_zsh_extended_prefix(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix(": ") {
+        if let Some((timing, command)) = rest.split_once(';') {
+            if timing.chars().all(|c| c.is_ascii_digit() || c == ':') {
+                return command;
+            }
+        }
+    }
+    line
+}
+
+
+/// Returns content with the bytes zsh escaped with ZSH_META restored
+fn unmetafy(content: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(content.len());
+    let mut iter = content.iter();
+    while let Some(&byte) = iter.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = iter.next() {
+                bytes.push(next ^ 32);
+            }
+        } else {
+            bytes.push(byte);
+        }
+    }
+    bytes
+}
+
+
+/// Returns the commands of a fish history, oldest first
+///
+/// Every entry starts with a `- cmd: <command>` line, followed by indented
+/// `when:` and `paths:` lines. Newlines and backslashes in the command are
+/// escaped.
+fn parse_fish(content: &str) -> Vec<String> {
+    content.lines()
+        .filter_map(|line| line.strip_prefix("- cmd: "))
+        .map(unescape_fish)
+        .collect()
+}
+
+
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('\\') => unescaped.push('\\'),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            },
+            None => unescaped.push('\\')
+        }
+    }
+    unescaped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn bash_history_is_distinct_and_most_recent_first() {
+        assert_eq!(parse_history(b"ls\ncd /tmp\nls\n\n", Shell::Bash), vec!["ls", "cd /tmp"]);
+    }
+
+
+    #[test]
+    fn bash_history_groups_lines_by_timestamp() {
+        assert_eq!(
+            parse_history(b"#1700000000\nfor i in 1 2\ndo echo $i; done\n#1700000001\npwd\n", Shell::Bash),
+            vec!["pwd", "for i in 1 2\ndo echo $i; done"]
+        );
+    }
+
+
+    #[test]
+    fn zsh_history_strips_prefix_and_joins_continuations() {
+        assert_eq!(
+            parse_history(b": 1700000000:0;echo a \\\nb\n: 1700000001:2;git status\nplain\n", Shell::Zsh),
+            vec!["plain", "git status", "echo a \nb"]
+        );
+    }
+
+
+    #[test]
+    fn zsh_history_is_unmetafied() {
+        // "é" is 0xc3 0xa9, zsh writes 0xa9 as ZSH_META followed by 0xa9 ^ 32
+        let content = [b"echo \xc3".as_ref(), &[ZSH_META, 0xa9 ^ 32], b"\n"].concat();
+        assert_eq!(parse_history(&content, Shell::Zsh), vec!["echo é"]);
+    }
+
+
+    #[test]
+    fn fish_history_unescapes_commands() {
+        assert_eq!(
+            parse_history(b"- cmd: echo a\\nb\n  when: 1700000000\n- cmd: echo c\\\\d\n  when: 1700000001\n", Shell::Fish),
+            vec!["echo c\\d", "echo a\nb"]
+        );
+    }
+
+
+    #[test]
+    fn shell_is_detected_from_file_name() {
+        assert_eq!(Shell::detect(Some("/home/me/.zsh_history")), Shell::Zsh);
+        assert_eq!(Shell::detect(Some("/home/me/.local/share/fish/fish_history")), Shell::Fish);
+    }
+}
@@ -0,0 +1,72 @@
+pub mod history;
+pub mod navi;
+pub mod pet;
+pub mod tldr;
+
+use std::{
+    fmt::{Display, Formatter},
+    fs::read_dir,
+    path::Path
+};
+
+use crate::error::{MemoireError, Result};
+
+
+/// A construct of an imported file that could not be converted, at the
+/// given line
+pub struct Warning {
+    path: String,
+    line: usize,
+    message: String,
+}
+
+
+impl Warning {
+    pub fn new(path: &str, line: usize, message: &str) -> Warning {
+        Warning {
+            path: path.to_owned(),
+            line,
+            message: message.to_owned()
+        }
+    }
+}
+
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{}, line {}: {}", self.path, self.line, self.message)
+    }
+}
+
+
+/// Returns the paths of the files ending with extension below path, sorted,
+/// or path itself if it is a file
+pub fn find_files(path: &str, extension: &str) -> Result<Vec<String>> {
+    if !Path::new(path).is_dir() {
+        return Ok(vec![path.to_owned()]);
+    }
+    let mut paths: Vec<String> = Vec::new();
+    let entries = read_dir(path).map_err(|err| MemoireError::Io(path.to_owned(), err))?;
+    for entry in entries {
+        let entry_path = entry.map_err(|err| MemoireError::Io(path.to_owned(), err))?.path();
+        let entry_path = entry_path.to_string_lossy().to_string();
+        if Path::new(&entry_path).is_dir() {
+            paths.append(&mut find_files(&entry_path, extension)?);
+        } else if entry_path.ends_with(extension) {
+            paths.push(entry_path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+
+/// Returns a valid collection name made from the name of the file at path,
+/// without extension
+pub fn get_collection_name(path: &str, extension: &str) -> String {
+    let file_name = Path::new(path).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let name: String = file_name.strip_suffix(extension).unwrap_or(&file_name).chars().map(
+        |c| if c.is_alphanumeric() || " -_.".contains(c) { c } else { '-' }
+    ).collect();
+    name.trim().trim_start_matches('.').to_owned()
+}
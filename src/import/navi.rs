@@ -0,0 +1,205 @@
+use std::fs::read_to_string;
+
+use super::{Warning, find_files, get_collection_name};
+use crate::collection::{bookmark::Bookmark, util::path_to_string};
+use crate::error::{MemoireError, Result};
+
+
+pub static CHEAT_EXTENSION: &str = ".cheat";
+
+
+/// Cheats of a `%` section, variables apply to every command of their section
+#[derive(Default)]
+struct Section {
+    tags: Vec<String>,
+    description: String,
+    command: Vec<String>,
+    bookmarks: Vec<Bookmark>,
+    // Name and command listing the values of every `$` variable
+    variables: Vec<(String, String)>,
+}
+
+
+impl Section {
+    fn new(tags: Vec<String>) -> Section {
+        Section {
+            tags,
+            ..Section::default()
+        }
+    }
+
+    /// Turn the command lines read so far into a bookmark
+    fn finish_command(&mut self, collection: &str) {
+        if !self.command.is_empty() {
+            self.bookmarks.push(Bookmark::new(&self.command.join("\n"), &self.description, &self.tags, collection));
+            self.command.clear();
+            self.description.clear();
+        }
+    }
+
+    /// Returns the bookmarks of the section, with the variables each command
+    /// uses added to its annotation
+    fn finish(mut self, collection: &str) -> Vec<Bookmark> {
+        self.finish_command(collection);
+        let variables = self.variables;
+        self.bookmarks.into_iter().map(|bookmark| {
+            let mut annotation = vec![bookmark.get_annotation().to_owned()];
+            for (name, values) in &variables {
+                if bookmark.get_command().contains(&format!("<{}>", name)) {
+                    annotation.push(format!("<{}> from: {}", name, values));
+                }
+            }
+            annotation.retain(|part| !part.is_empty());
+            Bookmark::new(bookmark.get_command(), &annotation.join("; "), bookmark.get_tags(), collection)
+        }).collect()
+    }
+}
+
+
+/// Returns the directory navi downloads cheats to
+pub fn get_cheats_path() -> Result<String> {
+    match dirs::data_dir() {
+        Some(data_dir) => path_to_string(data_dir.join("navi").join("cheats")),
+        None => Err(MemoireError::NoDataDir)
+    }
+}
+
+
+/// Returns the bookmarks of the navi cheat files at path, a file or a
+/// directory searched for `.cheat` files, with the constructs that were
+/// skipped
+///
+/// # Arguments
+///
+/// * `path` - The path of a cheat file or of a directory holding them
+/// * `collection` - The collection of every bookmark, by default the name of its file
+pub fn read_cheats(path: &str, collection: Option<&str>) -> Result<(Vec<Bookmark>, Vec<Warning>)> {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    for cheat_path in find_files(path, CHEAT_EXTENSION)? {
+        let content = read_to_string(&cheat_path).map_err(|err| MemoireError::Io(cheat_path.to_owned(), err))?;
+        let file_collection = match collection {
+            Some(collection) => collection.to_owned(),
+            None => get_collection_name(&cheat_path, CHEAT_EXTENSION)
+        };
+        let (mut file_bookmarks, mut file_warnings) = parse_cheat(&cheat_path, &content, &file_collection);
+        bookmarks.append(&mut file_bookmarks);
+        warnings.append(&mut file_warnings);
+    }
+    Ok((bookmarks, warnings))
+}
+
+
+/// Returns the bookmarks of a navi cheat file, with the constructs that were
+/// skipped
+///
+/// `%` lines give the tags of the cheats below them, `#` lines describe the
+/// next command and `$` lines define how the values of a `<variable>` are
+/// listed. Consecutive command lines form a single command.
+fn parse_cheat(cheat_path: &str, content: &str, collection: &str) -> (Vec<Bookmark>, Vec<Warning>) {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    let mut section = Section::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        let warn = |message: &str| Warning::new(cheat_path, index + 1, message);
+        if let Some(tags) = line.strip_prefix('%') {
+            let tags = tags.split(',').map(|tag| tag.trim().to_owned()).filter(|tag| !tag.is_empty()).collect();
+            bookmarks.append(&mut std::mem::replace(&mut section, Section::new(tags)).finish(collection));
+        } else if let Some(description) = line.strip_prefix('#') {
+            section.finish_command(collection);
+            section.description = description.trim().to_owned();
+        } else if line.starts_with(';') {
+            // Comment
+        } else if let Some(variable) = line.strip_prefix('$') {
+            section.finish_command(collection);
+            match variable.split_once(':') {
+                Some((name, values)) => {
+                    let (values, options) = match values.split_once("---") {
+                        Some((values, options)) => (values, Some(options)),
+                        None => (values, None)
+                    };
+                    if options.is_some() {
+                        warnings.push(warn(&format!("options of variable {} are not supported, kept the command only", name.trim())));
+                    }
+                    section.variables.push((name.trim().to_owned(), values.trim().to_owned()));
+                },
+                None => warnings.push(warn("variable without a command, skipped"))
+            }
+        } else if line.starts_with('@') {
+            section.finish_command(collection);
+            warnings.push(warn("extending other cheats is not supported, skipped"));
+        } else if line.trim().is_empty() {
+            section.finish_command(collection);
+        } else {
+            section.command.push(line.to_owned());
+        }
+    }
+    bookmarks.append(&mut section.finish(collection));
+    (bookmarks, warnings)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+
+    static CHEAT: &str = "% git, code
+
+# Change branch
+git checkout <branch>
+
+; Not imported
+# Show a file
+git show \\
+  HEAD:<file>
+
+$ branch: git branch --format='%(refname:short)' --- --header 'Branch'
+$ missing
+@ other
+";
+
+
+    #[test]
+    fn cheat_sections_become_bookmarks() {
+        let (bookmarks, warnings) = parse_cheat("git.cheat", CHEAT, "git");
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].get_command(), "git checkout <branch>");
+        assert_eq!(
+            bookmarks[0].get_annotation(),
+            "Change branch; <branch> from: git branch --format='%(refname:short)'"
+        );
+        assert_eq!(bookmarks[0].get_tags(), &vec!["git".to_owned(), "code".to_owned()]);
+        assert_eq!(bookmarks[1].get_command(), "git show \\\n  HEAD:<file>");
+        assert_eq!(bookmarks[1].get_annotation(), "Show a file");
+        let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(warnings, vec![
+            "git.cheat, line 11: options of variable branch are not supported, kept the command only",
+            "git.cheat, line 12: variable without a command, skipped",
+            "git.cheat, line 13: extending other cheats is not supported, skipped",
+        ]);
+    }
+
+
+    #[test]
+    fn cheat_files_become_collections() {
+        let dir = tempdir().unwrap();
+        create_dir(dir.path().join("tools")).unwrap();
+        write(dir.path().join("tools").join("docker.cheat"), "% docker\n\n# List containers\ndocker ps\n").unwrap();
+        write(dir.path().join("git.cheat"), CHEAT).unwrap();
+        write(dir.path().join("notes.txt"), "not a cheat").unwrap();
+        let dir_path = dir.path().to_str().unwrap();
+
+        let (bookmarks, _) = read_cheats(dir_path, None).unwrap();
+        let collections: Vec<&str> = bookmarks.iter().map(|bookmark| bookmark.get_collection().as_str()).collect();
+        assert_eq!(collections, vec!["git", "git", "docker"]);
+
+        let (bookmarks, _) = read_cheats(dir_path, Some("cheats")).unwrap();
+        assert!(bookmarks.iter().all(|bookmark| bookmark.get_collection() == "cheats"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::fs::read_to_string;
+
+use serde::Deserialize;
+
+use super::Warning;
+use crate::collection::bookmark::Bookmark;
+use crate::error::{MemoireError, Result};
+
+
+static SNIPPET_HEADER: &str = "[[snippets]]";
+
+
+/// Snippet file written by pet, usually `~/.config/pet/snippet.toml`
+#[derive(Deserialize)]
+struct SnippetFile {
+    #[serde(default)]
+    snippets: Vec<Snippet>,
+}
+
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct Snippet {
+    description: String,
+    command: String,
+    tag: Vec<String>,
+    // Example output of the command
+    output: String,
+}
+
+
+/// Returns the bookmarks of the pet snippet file at snippet_path, with the
+/// snippets that were skipped
+///
+/// The description of a snippet becomes the annotation, followed by its
+/// example output if it has one.
+///
+/// # Arguments
+///
+/// * `snippet_path` - The path of the snippet file
+/// * `collection` - The collection of the bookmarks
+pub fn read_snippets(snippet_path: &str, collection: &str) -> Result<(Vec<Bookmark>, Vec<Warning>)> {
+    let content = read_to_string(snippet_path).map_err(|err| MemoireError::Io(snippet_path.to_owned(), err))?;
+    parse_snippets(snippet_path, &content, collection)
+}
+
+
+/// Returns the bookmarks of a pet snippet file, with the snippets that were
+/// skipped
+fn parse_snippets(snippet_path: &str, content: &str, collection: &str) -> Result<(Vec<Bookmark>, Vec<Warning>)> {
+    let snippet_file: SnippetFile = toml::from_str(content).map_err(
+        |err| MemoireError::Import(snippet_path.to_owned(), err.to_string())
+    )?;
+    // Snippets are located by their header, toml does not keep positions
+    let header_lines: Vec<usize> = content.lines().enumerate()
+        .filter(|(_, line)| line.trim() == SNIPPET_HEADER)
+        .map(|(index, _)| index + 1)
+        .collect();
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    for (index, snippet) in snippet_file.snippets.into_iter().enumerate() {
+        if snippet.command.trim().is_empty() {
+            warnings.push(Warning::new(
+                snippet_path,
+                header_lines.get(index).copied().unwrap_or_default(),
+                "snippet without a command, skipped"
+            ));
+            continue;
+        }
+        let mut annotation = snippet.description.trim().to_owned();
+        if !snippet.output.trim().is_empty() {
+            if !annotation.is_empty() {
+                annotation.push_str("; ");
+            }
+            annotation.push_str(&format!("output: {}", snippet.output.trim()));
+        }
+        bookmarks.push(Bookmark::new(snippet.command.trim(), &annotation, &snippet.tag, collection));
+    }
+    Ok((bookmarks, warnings))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+
+    #[test]
+    fn snippets_become_bookmarks() {
+        let (bookmarks, warnings) = parse_snippets("snippet.toml", r#"
+[[snippets]]
+  description = "Count lines"
+  command = "wc -l <file>"
+  tag = ["text"]
+  output = "42 file"
+
+[[snippets]]
+  description = "Nothing to run"
+  command = " "
+
+[[snippets]]
+  command = "uptime"
+"#, "pet").unwrap();
+        assert_eq!(bookmarks.len(), 2);
+        assert_eq!(bookmarks[0].get_command(), "wc -l <file>");
+        assert_eq!(bookmarks[0].get_annotation(), "Count lines; output: 42 file");
+        assert_eq!(bookmarks[0].get_tags(), &vec!["text".to_owned()]);
+        assert_eq!(bookmarks[0].get_collection(), "pet");
+        assert_eq!(bookmarks[1].get_annotation(), "");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].to_string().ends_with("line 8: snippet without a command, skipped"));
+    }
+
+
+    #[test]
+    fn invalid_toml_fails() {
+        assert!(matches!(parse_snippets("snippet.toml", "[[snippets]\ncommand = ", "pet"), Err(MemoireError::Import(_, _))));
+    }
+}
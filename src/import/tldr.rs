@@ -0,0 +1,186 @@
+use std::{fs::read_to_string, path::Path};
+
+use super::{Warning, find_files, get_collection_name};
+use crate::collection::bookmark::Bookmark;
+use crate::error::{MemoireError, Result};
+
+
+pub static PAGE_EXTENSION: &str = ".md";
+// Collection of the pages when none is given
+pub static DEFAULT_COLLECTION: &str = "tldr";
+// Holds one directory of English pages per platform in a tldr checkout
+static PAGES_DIR_NAME: &str = "pages";
+// Mnemonics are short, longer bracketed text is kept as is
+const MAX_MNEMONIC_LENGTH: usize = 3;
+
+
+/// Returns the bookmarks of the tldr pages below dir_path, with the lines
+/// that could not be converted
+///
+/// dir_path is a tldr checkout, a directory of pages like `pages/linux` or a
+/// single page. `{{placeholder}}` arguments are kept verbatim.
+///
+/// # Arguments
+///
+/// * `dir_path` - The path of the pages
+/// * `collection` - The collection of every bookmark, tagged with its page, or None for a collection per page
+/// * `platforms` - The platforms to import, like common or linux, all if empty
+pub fn read_pages(dir_path: &str, collection: Option<&str>, platforms: &[&str]) -> Result<(Vec<Bookmark>, Vec<Warning>)> {
+    let pages_path = Path::new(dir_path).join(PAGES_DIR_NAME);
+    let dir_path = if pages_path.is_dir() { pages_path.to_string_lossy().to_string() } else { dir_path.to_owned() };
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    for page_path in find_files(&dir_path, PAGE_EXTENSION)? {
+        // Pages are grouped in a directory per platform
+        let platform = Path::new(&page_path).parent()
+            .and_then(|parent| parent.file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if !platforms.is_empty() && !platforms.contains(&platform.as_str()) {
+            continue;
+        }
+        let content = read_to_string(&page_path).map_err(|err| MemoireError::Io(page_path.to_owned(), err))?;
+        let page = get_collection_name(&page_path, PAGE_EXTENSION);
+        let (collection, tags) = match collection {
+            Some(collection) => (collection.to_owned(), vec![page, platform]),
+            None => (page, vec![platform])
+        };
+        let (mut page_bookmarks, mut page_warnings) = parse_page(&page_path, &content, &collection, &tags);
+        bookmarks.append(&mut page_bookmarks);
+        warnings.append(&mut page_warnings);
+    }
+    Ok((bookmarks, warnings))
+}
+
+
+/// Returns the bookmarks of a tldr page, with the lines that could not be
+/// converted
+///
+/// Every example is a `- description:` line followed by a backticked
+/// command, the title and `>` summary lines are skipped.
+fn parse_page(page_path: &str, content: &str, collection: &str, tags: &[String]) -> (Vec<Bookmark>, Vec<Warning>) {
+    let mut bookmarks: Vec<Bookmark> = Vec::new();
+    let mut warnings: Vec<Warning> = Vec::new();
+    // Description and line of the example waiting for its command
+    let mut description: Option<(String, usize)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if let Some(text) = line.strip_prefix("- ") {
+            if let Some((_, line)) = description {
+                warnings.push(Warning::new(page_path, line, "example without a command, skipped"));
+            }
+            description = Some((strip_mnemonics(text.trim_end_matches(':')), index + 1));
+        } else if line.len() > 1 && line.starts_with('`') && line.ends_with('`') {
+            let command = &line[1..line.len() - 1];
+            match description.take() {
+                Some((description, _)) => bookmarks.push(Bookmark::new(command, &description, &tags.to_vec(), collection)),
+                None => warnings.push(Warning::new(page_path, index + 1, "command without a description, skipped"))
+            }
+        }
+    }
+    if let Some((_, line)) = description {
+        warnings.push(Warning::new(page_path, line, "example without a command, skipped"));
+    }
+    (bookmarks, warnings)
+}
+
+
+/// Returns description without the brackets tldr puts around the letters of
+/// an option, like `[c]reate` for `-c`
+fn strip_mnemonics(description: &str) -> String {
+    let mut stripped = String::with_capacity(description.len());
+    let mut rest = description;
+    while let Some(start) = rest.find('[') {
+        stripped.push_str(&rest[..start]);
+        let bracketed = &rest[start + 1..];
+        match bracketed.find(']') {
+            Some(end) if end > 0
+                && end <= MAX_MNEMONIC_LENGTH
+                && bracketed[..end].chars().all(|c| c.is_alphanumeric()) => {
+                stripped.push_str(&bracketed[..end]);
+                rest = &bracketed[end + 1..];
+            },
+            _ => {
+                stripped.push('[');
+                rest = bracketed;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+
+    static TAR_PAGE: &str = "# tar
+
+> Archiving utility.
+
+- [c]reate an archive from [f]iles:
+
+`tar cf {{path/to/target.tar}} {{path/to/file}}`
+
+- Extract [optional step] files:
+
+`tar xf {{source.tar}}`
+
+- Run a command:
+
+`stray command`
+";
+
+
+    #[test]
+    fn examples_become_bookmarks() {
+        let tags = vec!["tar".to_owned(), "common".to_owned()];
+        let (bookmarks, warnings) = parse_page("tar.md", TAR_PAGE, "tldr", &tags);
+        assert_eq!(bookmarks.len(), 3);
+        assert_eq!(bookmarks[0].get_command(), "tar cf {{path/to/target.tar}} {{path/to/file}}");
+        assert_eq!(bookmarks[0].get_annotation(), "create an archive from files");
+        assert_eq!(bookmarks[0].get_tags(), &tags);
+        assert_eq!(bookmarks[1].get_annotation(), "Extract [optional step] files");
+        assert!(warnings.is_empty());
+    }
+
+
+    #[test]
+    fn unpaired_lines_are_reported() {
+        let (bookmarks, warnings) = parse_page("x.md", "- Orphan:\n\n- Second:\n\n`x`\n`y`\n- Last:\n", "tldr", &[]);
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].get_annotation(), "Second");
+        let warnings: Vec<String> = warnings.iter().map(|warning| warning.to_string()).collect();
+        assert_eq!(warnings, vec![
+            "x.md, line 1: example without a command, skipped",
+            "x.md, line 6: command without a description, skipped",
+            "x.md, line 7: example without a command, skipped",
+        ]);
+    }
+
+
+    #[test]
+    fn checkout_pages_are_tagged_or_split_by_page() {
+        let dir = tempdir().unwrap();
+        for (platform, page) in [("pages/common", "tar"), ("pages/linux", "apt"), ("pages.fr/common", "tar")] {
+            create_dir_all(dir.path().join(platform)).unwrap();
+            write(dir.path().join(platform).join(format!("{}.md", page)), "- Run:\n\n`run`\n").unwrap();
+        }
+        let dir_path = dir.path().to_str().unwrap();
+
+        let (bookmarks, _) = read_pages(dir_path, Some("tldr"), &[]).unwrap();
+        let tags: Vec<&Vec<String>> = bookmarks.iter().map(|bookmark| bookmark.get_tags()).collect();
+        assert_eq!(tags, vec![&vec!["tar".to_owned(), "common".to_owned()], &vec!["apt".to_owned(), "linux".to_owned()]]);
+
+        let (bookmarks, _) = read_pages(dir_path, None, &["linux"]).unwrap();
+        assert_eq!(bookmarks.len(), 1);
+        assert_eq!(bookmarks[0].get_collection(), "apt");
+        assert_eq!(bookmarks[0].get_tags(), &vec!["linux".to_owned()]);
+    }
+}
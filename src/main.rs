@@ -1,53 +1,161 @@
 mod term;
 mod collection;
+mod config;
+mod error;
+mod import;
+mod export;
 
-use std::{env, path::Path, process::{Command, Stdio, exit}};
-use clap::{Arg, App, SubCommand};
+use std::{collections::BTreeSet, env, fs, io::{stdin, stdout, Write}, process::exit, rc::Rc};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
 
 use collection::{
     bookmark::Bookmark,
-    jq::{SearchResult, add, delete, search, validate_jsons},
-    util::{
-        DEFAULT_JSON_NAME, create_collection_dir,
-        get_json_path, get_collection_dir_path
-    }
+    dedupe::{
+        find_duplicate_groups, find_duplicates, get_distinct_annotations, merge_annotations, merge_duplicates
+    },
+    doctor::diagnose,
+    journal::{JournalEntry, Operation, revert},
+    migration::import_legacy_history,
+    store::{SearchMode, SearchResult, SortKey, Store, open_store},
+    util::{format_time, get_collection_dir_path, get_legacy_history_path}
+};
+use config::{Config, parse_color};
+use export::markdown::render_cheatsheet;
+use import::{
+    Warning,
+    history::{Shell, read_history},
+    navi::{get_cheats_path, read_cheats},
+    pet::read_snippets,
+    tldr::{DEFAULT_COLLECTION as TLDR_COLLECTION, read_pages}
 };
+use error::{MemoireError, Result};
 use term::Term;
 
 
 fn main() {
-    // Create Collection path if does not exist
-    if !Path::new(&get_json_path(DEFAULT_JSON_NAME)).exists() {
-        create_collection_dir(&get_collection_dir_path());
-    }
-
-    // Check if jq is installed
-    let status = Command::new("bash")
-                         .arg("-c")
-                         .arg("jq --version")
-                         .stdout(Stdio::null())
-                         .stderr(Stdio::null())
-                         .status()
-                         .expect("jq command not found");
-    if !status.success() {
-        println!("Unable to execute `jq`");
-        println!("Please make sure `jq` is correctly installed.");
-        println!("For more Details see: https://stedolan.github.io/jq/");
+    if let Err(err) = run() {
+        eprintln!("{}", err);
         exit(1);
     }
+}
+
 
-    // Validate all json format
-    validate_jsons(&get_collection_dir_path());
+fn run() -> Result<()> {
+    let matches = build_app().get_matches_from(env::args());
+    let sort_key = match matches.value_of("Sort") {
+        Some(key) => Some(key.parse::<SortKey>()?),
+        None => None
+    };
 
-    let mut term = Term::new();
-    term.get_mut_widget_manager().update_result_table(parse_input(env::args().collect()));
+    let config = Rc::new(Config::load()?);
 
-    term.display().unwrap();
+    // Creates the collection directory if it does not exist
+    let dir_path = get_collection_dir_path(matches.value_of("Dir"), config.get_dir())?;
+    // Runs before the store is opened, opening it already repairs some files
+    if let Some(matches) = matches.subcommand_matches("doctor") {
+        if !run_doctor(&dir_path, config.get_default_collection(), matches.is_present("Fix"))? {
+            exit(1);
+        }
+        return Ok(());
+    }
+    let store: Rc<dyn Store> = Rc::from(
+        open_store(&dir_path, config.get_store(), config.get_default_collection())?
+    );
+    // Printed before the TUI starts so they are still on screen after it quits
+    for collection in store.get_broken_collections() {
+        eprintln!("Warning: skipped collection {}: {}", collection.get_name(), collection.get_error());
+    }
+    if let Some(err) = store.get_trash_error() {
+        eprintln!("Warning: {}", err);
+    }
+
+    // Look for import subcommands, picking what to import opens its own TUI
+    if let Some(matches) = matches.subcommand_matches("import") {
+        return run_import(store, config, matches);
+    }
+
+    // Commands that only print their output do not open the TUI
+    if let Some(results) = parse_input(store.as_ref(), &config, &matches)? {
+        // NOTE: term is dropped before returning so the terminal is always restored
+        let mut term = Term::new(store, config)?;
+        term.get_mut_widget_manager().set_sort_key(sort_key);
+        term.get_mut_widget_manager().update_result_table(results);
+        term.display()?;
+    }
+    Ok(())
 }
 
 
-fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
-    let app = App::new("Memoire")
+fn parse_index(matches: &clap::ArgMatches, name: &str) -> Result<usize> {
+    let index: String = matches.values_of(name).unwrap().collect();
+    index.parse().map_err(
+        |_err| MemoireError::InvalidArgument(format!("{} is not a valid index", index))
+    )
+}
+
+
+fn parse_id(matches: &clap::ArgMatches) -> String {
+    matches.values_of("Id").unwrap().collect()
+}
+
+
+/// Returns the bookmark with the id given in matches
+fn find_by_id(store: &dyn Store, matches: &clap::ArgMatches) -> Result<SearchResult> {
+    let id = parse_id(matches);
+    store.find(&id)?.ok_or_else(
+        || MemoireError::InvalidArgument(format!("No bookmark with id {}", id))
+    )
+}
+
+
+fn print_bookmark(bookmark: &Bookmark) {
+    println!("Id: {}", bookmark.get_id());
+    println!("Command: {}", bookmark.get_command());
+    println!("Annotation: {}", bookmark.get_annotation());
+    println!("Tags: {}", bookmark.get_tags_as_string(", "));
+    println!("Collection: {}", bookmark.get_collection());
+    println!("Created: {}", format_time(bookmark.get_created_at()));
+    println!("Updated: {}", format_time(bookmark.get_updated_at()));
+    println!("Last used: {}", format_time(bookmark.get_last_used_at()));
+    println!("Uses: {}", bookmark.get_use_count());
+}
+
+
+/// Print the metadata, size, tags and usage of collection
+fn print_collection_info(store: &dyn Store, collection: &str) -> Result<()> {
+    if !store.collections()?.iter().any(|name| name == collection) {
+        return Err(MemoireError::InvalidArgument(format!("No collection named {}", collection)));
+    }
+    let results = store.list(collection)?;
+    let bookmarks: Vec<&Bookmark> = results.iter().map(|result| result.get_bookmark()).collect();
+    let tags: BTreeSet<&str> = bookmarks.iter()
+        .flat_map(|bookmark| bookmark.get_tags().iter().map(|tag| tag.as_str()))
+        .collect();
+    let metadata = store.get_metadata(collection)?;
+    println!("Name: {}", collection);
+    println!("Description: {}", metadata.get_description());
+    println!("Owner: {}", metadata.get_owner());
+    println!("Color: {}", metadata.get_color().unwrap_or_default());
+    println!("Icon: {}", metadata.get_icon());
+    println!("Priority: {}", metadata.get_priority());
+    println!("Read-only: {}", if store.is_read_only(collection)? { "yes" } else { "no" });
+    println!("Bookmarks: {}", bookmarks.len());
+    println!("Tags: {}", tags.into_iter().collect::<Vec<&str>>().join(", "));
+    println!(
+        "Last updated: {}",
+        format_time(bookmarks.iter().filter_map(|bookmark| bookmark.get_updated_at()).max())
+    );
+    println!(
+        "Last used: {}",
+        format_time(bookmarks.iter().filter_map(|bookmark| bookmark.get_last_used_at()).max())
+    );
+    println!("Uses: {}", bookmarks.iter().map(|bookmark| bookmark.get_use_count()).sum::<u64>());
+    Ok(())
+}
+
+
+fn build_app() -> App<'static, 'static> {
+    App::new("Memoire")
         .version("0.1")
         .author("Phoenix Chen")
         .about("A CLI for bookmarking commands")
@@ -90,7 +198,19 @@ fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
             .short("i")
             .long("intersection")
             .takes_value(false)
-            .help("Return search results with intersection (default union)")
+            .help("Return search results with intersection (default union, see search_mode in the configuration)")
+        )
+        .arg(Arg::with_name("Dir")
+            .long("dir")
+            .takes_value(true)
+            .global(true)
+            .help("Directory holding the collections (default $MEMOIRE_HOME, ~/.memoire or the XDG data directory)")
+        )
+        .arg(Arg::with_name("Sort")
+            .long("sort")
+            .takes_value(true)
+            .possible_values(&["created", "updated", "used", "count"])
+            .help("Sort results by most recently created, updated, used or most used")
         )
         .subcommand(SubCommand::with_name("--add")
             .about("Add bookmark")
@@ -121,34 +241,34 @@ fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
                 .long("collection")
                 .takes_value(true)
                 .multiple(true)
-                .help("Search with command")
+                .help("Collection of the bookmark (default is default_collection in the configuration, \"default\" unless set)")
             )
         )
         .subcommand(SubCommand::with_name("--delete")
             .about("Delete bookmark")
-            .arg(Arg::with_name("Index")
+            .arg(Arg::with_name("Id")
                 .short("i")
                 .takes_value(true)
-                .long("index")
-                .required(true)
-                .help("Index of the bookmark")
-            )
-            .arg(Arg::with_name("Collection")
-                .short("c")
-                .takes_value(true)
-                .long("collection")
+                .long("id")
                 .required(true)
-                .help("Collection of the bookmark")
+                .help("Id of the bookmark")
             )
         )
         .subcommand(SubCommand::with_name("--edit")
             .about("Edit exisiting bookmark")
-            .arg(Arg::with_name("Index")
+            .arg(Arg::with_name("Id")
                 .short("i")
                 .takes_value(true)
-                .long("index")
+                .long("id")
                 .required(true)
-                .help("Index of the bookmark")
+                .help("Id of the bookmark")
+            )
+            .arg(Arg::with_name("From")
+                .short("f")
+                .long("from")
+                .takes_value(true)
+                .multiple(true)
+                .help("Collection the bookmark is in, the edit fails if it is elsewhere")
             )
             .arg(Arg::with_name("Tags")
                 .short("t")
@@ -176,11 +296,792 @@ fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
                 .long("collection")
                 .takes_value(true)
                 .multiple(true)
-                .help("New collection")
+                .help("Collection to move the bookmark to (default its current collection)")
+            )
+        )
+        .subcommand(SubCommand::with_name("--move")
+            .about("Move bookmark to another position within its collection")
+            .arg(Arg::with_name("Id")
+                .short("i")
+                .takes_value(true)
+                .long("id")
+                .required(true)
+                .help("Id of the bookmark")
             )
+            .arg(Arg::with_name("To")
+                .short("t")
+                .takes_value(true)
+                .long("to")
+                .required(true)
+                .help("New index of the bookmark")
+            )
+        )
+        .subcommand(SubCommand::with_name("--show")
+            .about("Print bookmark")
+            .arg(Arg::with_name("Id")
+                .short("i")
+                .takes_value(true)
+                .long("id")
+                .required(true)
+                .help("Id of the bookmark")
+            )
+        )
+        .subcommand(SubCommand::with_name("--import-legacy")
+            .about("Import bookmarks saved by memoire below 0.1.2 into the default collection")
+            .arg(Arg::with_name("File")
+                .short("f")
+                .takes_value(true)
+                .long("file")
+                .help("Path of the history file (default ~/.memoir_history.json)")
+            )
+        )
+        .subcommand(SubCommand::with_name("import")
+            .about("Import bookmarks from other tools")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("history")
+                .about("Pick commands from a shell history to bookmark, with an annotation, tags and collection given to all of them")
+                .arg(Arg::with_name("Shell")
+                    .long("shell")
+                    .takes_value(true)
+                    .possible_values(&["bash", "zsh", "fish"])
+                    .help("Shell that wrote the history (default guessed from the file name or $SHELL)")
+                )
+                .arg(Arg::with_name("File")
+                    .short("f")
+                    .long("file")
+                    .takes_value(true)
+                    .help("Path of the history file (default the one of the shell)")
+                )
+                .arg(Arg::with_name("Annotation")
+                    .short("a")
+                    .long("annotation")
+                    .takes_value(true)
+                    .help("Annotation of the imported bookmarks")
+                )
+                .arg(Arg::with_name("Tags")
+                    .short("t")
+                    .long("tags")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Tags of the imported bookmarks")
+                )
+                .arg(Arg::with_name("Collection")
+                    .long("collection")
+                    .takes_value(true)
+                    .help("Collection of the imported bookmarks (default is default_collection in the configuration)")
+                )
+                .arg(Arg::with_name("All")
+                    .long("all")
+                    .takes_value(false)
+                    .help("Import every command not bookmarked yet without picking them")
+                )
+            )
+            .subcommand(SubCommand::with_name("navi")
+                .about("Import navi cheats, every cheat file becoming a collection")
+                .arg(Arg::with_name("Path")
+                    .multiple(true)
+                    .help("Cheat files or directories searched for them (default the cheats downloaded by navi)")
+                )
+                .arg(Arg::with_name("Collection")
+                    .long("collection")
+                    .takes_value(true)
+                    .help("Import every cheat into this collection instead")
+                )
+            )
+            .subcommand(SubCommand::with_name("tldr")
+                .about("Import the examples of tldr pages, tagged with their page and platform")
+                .arg(Arg::with_name("Path")
+                    .required(true)
+                    .help("Path of a tldr checkout, a directory of pages or a single page")
+                )
+                .arg(Arg::with_name("Collection")
+                    .long("collection")
+                    .takes_value(true)
+                    .conflicts_with("PerPage")
+                    .help("Collection of the imported bookmarks (default tldr)")
+                )
+                .arg(Arg::with_name("PerPage")
+                    .long("per-page")
+                    .takes_value(false)
+                    .help("Import every page into a collection named after it, tagged with its platform")
+                )
+                .arg(Arg::with_name("Platform")
+                    .short("p")
+                    .long("platform")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Only import pages of these platforms, like common or linux")
+                )
+            )
+            .subcommand(SubCommand::with_name("pet")
+                .about("Import pet snippets, skipping those already in the collection")
+                .arg(Arg::with_name("File")
+                    .required(true)
+                    .help("Path of the snippet file, usually ~/.config/pet/snippet.toml")
+                )
+                .arg(Arg::with_name("Collection")
+                    .long("collection")
+                    .takes_value(true)
+                    .help("Collection of the imported bookmarks (default is default_collection in the configuration)")
+                )
+            )
+        )
+        .subcommand(SubCommand::with_name("export")
+            .about("Export bookmarks for other tools")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("markdown")
+                .about("Export bookmarks as a markdown cheatsheet grouped by collection and tag")
+                .arg(Arg::with_name("Collection")
+                    .long("collection")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Only export these collections")
+                )
+                .arg(Arg::with_name("Tag")
+                    .long("tag")
+                    .takes_value(true)
+                    .multiple(true)
+                    .help("Only export bookmarks having one of these tags")
+                )
+                .arg(Arg::with_name("Output")
+                    .short("o")
+                    .long("output")
+                    .takes_value(true)
+                    .help("Path of the file to write (default standard output)")
+                )
+            )
+        )
+        .subcommand(SubCommand::with_name("collection")
+            .about("Manage collections")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("List collections with their number of bookmarks")
+            )
+            .subcommand(SubCommand::with_name("create")
+                .about("Create an empty collection")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+            )
+            .subcommand(SubCommand::with_name("rename")
+                .about("Rename collection")
+                .arg(Arg::with_name("From")
+                    .required(true)
+                    .help("Current name of the collection")
+                )
+                .arg(Arg::with_name("To")
+                    .required(true)
+                    .help("New name of the collection")
+                )
+            )
+            .subcommand(SubCommand::with_name("merge")
+                .about("Move every bookmark of a collection to the end of another and delete it")
+                .arg(Arg::with_name("From")
+                    .required(true)
+                    .help("Collection to merge and delete")
+                )
+                .arg(Arg::with_name("Into")
+                    .required(true)
+                    .help("Collection receiving the bookmarks")
+                )
+            )
+            .subcommand(SubCommand::with_name("delete")
+                .about("Delete collection, moving its bookmarks to the trash")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+                .arg(Arg::with_name("Force")
+                    .short("f")
+                    .long("force")
+                    .takes_value(false)
+                    .help("Delete the collection even if it is not empty")
+                )
+            )
+            .subcommand(SubCommand::with_name("info")
+                .about("Print the metadata, size, tags and usage of collection")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+            )
+            .subcommand(SubCommand::with_name("set")
+                .about("Set the description, owner, color, icon or priority of collection, an empty value clears it")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+                .arg(Arg::with_name("Description")
+                    .short("d")
+                    .long("description")
+                    .takes_value(true)
+                    .help("What the collection is for, shown in the TUI")
+                )
+                .arg(Arg::with_name("Owner")
+                    .long("owner")
+                    .takes_value(true)
+                    .help("Person or team maintaining the collection")
+                )
+                .arg(Arg::with_name("Color")
+                    .long("color")
+                    .takes_value(true)
+                    .help("Color of the collection in the TUI, a name like lightgreen or #rrggbb")
+                )
+                .arg(Arg::with_name("Icon")
+                    .long("icon")
+                    .takes_value(true)
+                    .help("Shown before the collection name in the TUI")
+                )
+                .arg(Arg::with_name("Priority")
+                    .long("priority")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .help("Collections with a higher priority rank first in the TUI (default 0)")
+                )
+            )
+            .subcommand(SubCommand::with_name("protect")
+                .about("Mark collection read-only, so its bookmarks can not be added, edited, moved or deleted")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+            )
+            .subcommand(SubCommand::with_name("unprotect")
+                .about("Allow changes to a collection marked read-only")
+                .arg(Arg::with_name("Name")
+                    .required(true)
+                    .help("Name of the collection")
+                )
+            )
+        )
+        .subcommand(SubCommand::with_name("trash")
+            .about("Manage deleted bookmarks")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                .about("List deleted bookmarks, most recently deleted first")
+            )
+            .subcommand(SubCommand::with_name("restore")
+                .about("Put deleted bookmarks back where they were in their collection")
+                .arg(Arg::with_name("Id")
+                    .required(true)
+                    .multiple(true)
+                    .help("Ids of the bookmarks to restore")
+                )
+            )
+            .subcommand(SubCommand::with_name("empty")
+                .about("Permanently remove every deleted bookmark")
+            )
+        )
+        .subcommand(SubCommand::with_name("log")
+            .about("Show the changes made to bookmarks, most recent first")
+            .arg(Arg::with_name("Limit")
+                .short("n")
+                .long("limit")
+                .takes_value(true)
+                .help("Number of entries to show")
+            )
+            .arg(Arg::with_name("Collection")
+                .long("collection")
+                .takes_value(true)
+                .help("Only show the changes made to this collection")
+            )
+        )
+        .subcommand(SubCommand::with_name("revert")
+            .about("Undo the change recorded in a journal entry")
+            .arg(Arg::with_name("Entry")
+                .required(true)
+                .help("Number of the entry, as shown by memoire log")
+            )
+        )
+        .subcommand(SubCommand::with_name("dedupe")
+            .about("Merge bookmarks running the same command, ignoring quoting and spacing")
+            .arg(Arg::with_name("Yes")
+                .short("y")
+                .long("yes")
+                .takes_value(false)
+                .help("Keep the first bookmark of each group and merge all annotations without asking")
+            )
+        )
+        .subcommand(SubCommand::with_name("doctor")
+            .about("Check the collection directory for broken files, duplicates and stray files")
+            .arg(Arg::with_name("Fix")
+                .short("f")
+                .long("fix")
+                .takes_value(false)
+                .help("Repair the issues that can be repaired, keeping a backup of every file changed")
+            )
+        )
+}
+
+
+/// Print the issues found in the collection directory, repairing them if fix
+/// is true
+///
+/// Returns false if issues are left
+fn run_doctor(dir_path: &str, default_collection: &str, fix: bool) -> Result<bool> {
+    let issues = diagnose(dir_path, default_collection, fix)?;
+    if issues.is_empty() {
+        println!("No issues found in {}", dir_path);
+        return Ok(true);
+    }
+    for issue in &issues {
+        println!("{}: {}", issue.get_path(), issue.get_description());
+        match (issue.get_repair(), fix) {
+            (Some(repair), true) => println!("    fixed: {}", repair),
+            (Some(repair), false) => println!("    fix: {}", repair),
+            (None, _) => println!("    repair by hand")
+        }
+    }
+    let fixable = issues.iter().filter(|issue| issue.get_repair().is_some()).count();
+    if fix {
+        println!("Fixed {} issues, {} left to repair by hand", fixable, issues.len() - fixable);
+        Ok(fixable == issues.len())
+    } else {
+        println!("Found {} issues, memoire doctor --fix can repair {} of them", issues.len(), fixable);
+        Ok(false)
+    }
+}
+
+
+fn parse_collection_input(store: &dyn Store, matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(_)) => {
+            for collection in store.collections()? {
+                let size = store.list(&collection)?.len();
+                if store.is_read_only(&collection)? {
+                    println!("{} ({}, read-only)", collection, size);
+                } else {
+                    println!("{} ({})", collection, size);
+                }
+            }
+            for collection in store.get_broken_collections() {
+                println!("{} (broken)", collection.get_name());
+            }
+        },
+        ("create", Some(matches)) => {
+            let name = matches.value_of("Name").unwrap();
+            store.create_collection(name)?;
+            println!("Created collection {}", name);
+        },
+        ("rename", Some(matches)) => {
+            let (from, to) = (matches.value_of("From").unwrap(), matches.value_of("To").unwrap());
+            let count = store.rename_collection(from, to)?;
+            println!("Renamed collection {} to {} ({} bookmarks)", from, to, count);
+        },
+        ("merge", Some(matches)) => {
+            let (from, into) = (matches.value_of("From").unwrap(), matches.value_of("Into").unwrap());
+            let count = store.merge_collections(from, into)?;
+            println!("Merged {} bookmarks from {} into {}", count, from, into);
+        },
+        ("delete", Some(matches)) => {
+            let name = matches.value_of("Name").unwrap();
+            let size = store.list(name)?.len();
+            if size > 0 && !matches.is_present("Force") {
+                return Err(MemoireError::InvalidArgument(format!(
+                    "Collection {} has {} bookmarks, use --force to delete it anyway", name, size
+                )));
+            }
+            let count = store.delete_collection(name)?;
+            println!("Deleted collection {}, moved its {} bookmarks to the trash", name, count);
+        },
+        ("info", Some(matches)) => {
+            print_collection_info(store, matches.value_of("Name").unwrap())?;
+        },
+        ("set", Some(matches)) => {
+            let name = matches.value_of("Name").unwrap();
+            let mut metadata = store.get_metadata(name)?;
+            if let Some(description) = matches.value_of("Description") {
+                metadata.set_description(description);
+            }
+            if let Some(owner) = matches.value_of("Owner") {
+                metadata.set_owner(owner);
+            }
+            if let Some(color) = matches.value_of("Color") {
+                if !color.is_empty() && parse_color(color).is_none() {
+                    return Err(MemoireError::InvalidArgument(
+                        format!("Unknown color {}, expected a name like lightgreen or #rrggbb", color)
+                    ));
+                }
+                metadata.set_color(Some(color).filter(|color| !color.is_empty()));
+            }
+            if let Some(icon) = matches.value_of("Icon") {
+                metadata.set_icon(icon);
+            }
+            if let Some(priority) = matches.value_of("Priority") {
+                metadata.set_priority(if priority.is_empty() {
+                    0
+                } else {
+                    priority.parse::<i64>().map_err(
+                        |_err| MemoireError::InvalidArgument(format!("{} is not a valid priority", priority))
+                    )?
+                });
+            }
+            store.set_metadata(name, &metadata)?;
+            println!("Updated collection {}", name);
+        },
+        ("protect", Some(matches)) => {
+            let name = matches.value_of("Name").unwrap();
+            let mut metadata = store.get_metadata(name)?;
+            metadata.set_read_only(true);
+            store.set_metadata(name, &metadata)?;
+            println!("Collection {} is now read-only", name);
+        },
+        ("unprotect", Some(matches)) => {
+            let name = matches.value_of("Name").unwrap();
+            let mut metadata = store.get_metadata(name)?;
+            metadata.set_read_only(false);
+            store.set_metadata(name, &metadata)?;
+            if store.is_read_only(name)? {
+                println!("Collection {} stays read-only, its file is not writable", name);
+            } else {
+                println!("Collection {} can be changed again", name);
+            }
+        },
+        _ => unreachable!("collection requires a subcommand")
+    }
+    Ok(())
+}
+
+
+fn parse_trash_input(store: &dyn Store, matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("list", Some(_)) => {
+            for trashed in store.list_trash()? {
+                let bookmark = trashed.get_bookmark();
+                println!(
+                    "{}  {}  {}  {}",
+                    bookmark.get_id(),
+                    format_time(Some(trashed.get_deleted_at())),
+                    bookmark.get_collection(),
+                    bookmark.get_command()
+                );
+            }
+        },
+        ("restore", Some(matches)) => {
+            for id in matches.values_of("Id").unwrap() {
+                let result = store.restore(id)?;
+                println!(
+                    "Restored bookmark {} to collection {} at index {}",
+                    id, result.get_bookmark().get_collection(), result.get_index()
+                );
+            }
+        },
+        ("empty", Some(_)) => {
+            let count = store.empty_trash()?;
+            println!("Removed {} bookmarks from the trash", count);
+        },
+        _ => unreachable!("trash requires a subcommand")
+    }
+    Ok(())
+}
+
+
+fn run_import(store: Rc<dyn Store>, config: Rc<Config>, matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("history", Some(matches)) => import_history(store, config, matches),
+        ("navi", Some(matches)) => {
+            let paths: Vec<String> = match matches.values_of("Path") {
+                Some(paths) => paths.map(|path| path.to_owned()).collect(),
+                None => vec![get_cheats_path()?]
+            };
+            let mut bookmarks: Vec<Bookmark> = Vec::new();
+            let mut warnings: Vec<Warning> = Vec::new();
+            for path in &paths {
+                let (mut path_bookmarks, mut path_warnings) = read_cheats(path, matches.value_of("Collection"))?;
+                bookmarks.append(&mut path_bookmarks);
+                warnings.append(&mut path_warnings);
+            }
+            add_imported(store.as_ref(), &bookmarks, &warnings, "navi")
+        },
+        ("tldr", Some(matches)) => {
+            let collection = if matches.is_present("PerPage") {
+                None
+            } else {
+                Some(matches.value_of("Collection").unwrap_or(TLDR_COLLECTION))
+            };
+            let platforms: Vec<&str> = matches.values_of("Platform").map_or(Vec::new(), |platforms| platforms.collect());
+            let (bookmarks, warnings) = read_pages(matches.value_of("Path").unwrap(), collection, &platforms)?;
+            add_imported(store.as_ref(), &bookmarks, &warnings, "tldr")
+        },
+        ("pet", Some(matches)) => {
+            let collection = matches.value_of("Collection").unwrap_or_else(|| config.get_default_collection());
+            let (snippets, warnings) = read_snippets(matches.value_of("File").unwrap(), collection)?;
+            let mut bookmarks: Vec<Bookmark> = store.list(collection)?.into_iter().map(
+                |result| result.get_bookmark().to_owned()
+            ).collect();
+            let existing = bookmarks.len();
+            let mut skipped = 0;
+            for snippet in snippets {
+                if bookmarks.iter().any(|bookmark| bookmark.is_duplicate_of(&snippet)) {
+                    skipped += 1;
+                } else {
+                    bookmarks.push(snippet);
+                }
+            }
+            if skipped > 0 {
+                println!("Skipped {} snippets already in collection {}", skipped, collection);
+            }
+            add_imported(store.as_ref(), &bookmarks[existing..], &warnings, "pet")
+        },
+        _ => unreachable!("import requires a subcommand")
+    }
+}
+
+
+/// Write the bookmarks in the format of the export subcommand, to standard
+/// output unless --output is given
+fn run_export(store: &dyn Store, matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("markdown", Some(matches)) => {
+            let collections: Vec<&str> = matches.values_of("Collection").map_or(Vec::new(), |values| values.collect());
+            let tags: Vec<&str> = matches.values_of("Tag").map_or(Vec::new(), |values| values.collect());
+            let cheatsheet = render_cheatsheet(store, &collections, &tags)?;
+            if cheatsheet.is_empty() {
+                eprintln!("No bookmarks to export");
+            }
+            match matches.value_of("Output") {
+                Some(path) => fs::write(path, cheatsheet).map_err(|err| MemoireError::Io(path.to_owned(), err)),
+                None => {
+                    print!("{}", cheatsheet);
+                    Ok(())
+                }
+            }
+        },
+        _ => unreachable!("export requires a subcommand")
+    }
+}
+
+
+/// Add bookmarks read from the files of another tool, after printing what
+/// could not be converted
+fn add_imported(store: &dyn Store, bookmarks: &[Bookmark], warnings: &[Warning], tool: &str) -> Result<()> {
+    for warning in warnings {
+        eprintln!("Warning: {}", warning);
+    }
+    if bookmarks.is_empty() {
+        println!("No {} bookmarks to import", tool);
+        return Ok(());
+    }
+    let mut collections: BTreeSet<&str> = BTreeSet::new();
+    for bookmark in bookmarks {
+        store.add(bookmark, None)?;
+        collections.insert(bookmark.get_collection());
+    }
+    let collections: Vec<&str> = collections.into_iter().collect();
+    println!(
+        "Imported {} {} bookmarks into {}",
+        bookmarks.len(),
+        tool,
+        match collections.as_slice() {
+            [collection] => format!("collection {}", collection),
+            collections => format!("{} collections", collections.len())
+        }
+    );
+    Ok(())
+}
+
+
+/// Bookmark the commands of a shell history that are not bookmarked yet,
+/// picked in the TUI unless --all is given
+fn import_history(store: Rc<dyn Store>, config: Rc<Config>, matches: &ArgMatches) -> Result<()> {
+    let shell = match matches.value_of("Shell") {
+        Some(shell) => shell.parse::<Shell>()?,
+        None => Shell::detect(matches.value_of("File"))
+    };
+    let history_path = match matches.value_of("File") {
+        Some(history_path) => history_path.to_owned(),
+        None => shell.get_history_path()?
+    };
+    let tags: Vec<String> = matches.values_of("Tags").map_or(Vec::new(), |tags| tags.map(|tag| tag.to_owned()).collect());
+    let template = Bookmark::new(
+        "",
+        matches.value_of("Annotation").unwrap_or_default(),
+        &tags,
+        matches.value_of("Collection").unwrap_or_else(|| config.get_default_collection())
+    );
+    // Searched once, a history holds far more commands than a collection
+    let bookmarked: Vec<Bookmark> = store.search(&[""], SearchMode::Union)?.into_iter().map(
+        |result| result.get_bookmark().to_owned()
+    ).collect();
+    let candidates: Vec<SearchResult> = read_history(&history_path, shell)?.into_iter().map(
+        |command| Bookmark::new(&command, template.get_annotation(), &tags, template.get_collection())
+    ).filter(
+        |bookmark| !bookmarked.iter().any(|other| other.is_duplicate_of(bookmark))
+    ).enumerate().map(|(index, bookmark)| SearchResult::new(index, bookmark)).collect();
+    if candidates.is_empty() {
+        println!("No commands in {} that are not bookmarked yet", history_path);
+        return Ok(());
+    }
+    let bookmarks: Vec<Bookmark> = if matches.is_present("All") {
+        candidates.into_iter().map(|candidate| candidate.get_bookmark().to_owned()).collect()
+    } else {
+        // NOTE: term is dropped before printing so the output stays on screen
+        let picked = Term::new(Rc::clone(&store), config)?.pick(candidates, &template)?;
+        match picked {
+            Some(bookmarks) => bookmarks,
+            None => {
+                println!("Nothing imported");
+                return Ok(());
+            }
+        }
+    };
+    for bookmark in &bookmarks {
+        store.add(bookmark, None)?;
+    }
+    println!("Imported {} commands from {} history {}", bookmarks.len(), shell, history_path);
+    Ok(())
+}
+
+
+/// Merge every group of duplicate bookmarks, asking which bookmark to keep
+/// and which annotation to use unless assume_yes is true
+fn run_dedupe(store: &dyn Store, assume_yes: bool) -> Result<()> {
+    let groups = find_duplicate_groups(store)?;
+    if groups.is_empty() {
+        println!("No duplicate bookmarks found");
+        return Ok(());
+    }
+    for (number, group) in groups.iter().enumerate() {
+        println!("Duplicates {}/{}:", number + 1, groups.len());
+        for (index, result) in group.iter().enumerate() {
+            let bookmark = result.get_bookmark();
+            println!("  [{}] {}  {}  {}", index + 1, bookmark.get_id(), bookmark.get_collection(), bookmark.get_command());
+            println!("      Annotation: {}", bookmark.get_annotation());
+            println!("      Tags: {}", bookmark.get_tags_as_string(", "));
+        }
+        let keep = if assume_yes {
+            0
+        } else {
+            match prompt_choice(
+                &format!("Keep which bookmark? [1-{}, s to skip, q to quit] ", group.len()),
+                group.len(),
+                &["s", "q"]
+            )? {
+                Choice::Index(index) => index,
+                Choice::Other(answer) if answer == "s" => continue,
+                Choice::Other(_) => return Ok(())
+            }
+        };
+        // Only asked when the duplicates have different annotations
+        let merged_annotation = merge_annotations(group);
+        let annotation = if assume_yes || get_distinct_annotations(group).len() < 2 {
+            merged_annotation
+        } else {
+            match prompt_choice(
+                &format!("Use which annotation? [1-{}, m to merge them] ", group.len()),
+                group.len(),
+                &["m"]
+            )? {
+                Choice::Index(index) => group[index].get_bookmark().get_annotation().to_owned(),
+                Choice::Other(_) => merged_annotation
+            }
+        };
+        let merged = match merge_duplicates(store, group, keep, &annotation) {
+            Ok(merged) => merged,
+            Err(MemoireError::ReadOnly(collection)) => {
+                println!("Skipped, collection {} is read-only", collection);
+                continue;
+            },
+            Err(err) => return Err(err)
+        };
+        println!(
+            "Merged into bookmark {}, moved {} duplicates to the trash",
+            merged.get_id(), group.len() - 1
+        );
+    }
+    Ok(())
+}
+
+
+/// Answer to a prompt
+enum Choice {
+    /// Zero based index of the item picked
+    Index(usize),
+    /// One of the other answers allowed
+    Other(String)
+}
+
+
+/// Ask question until the answer is a number between 1 and count or one of
+/// others, the end of input counts as the last of others
+fn prompt_choice(question: &str, count: usize, others: &[&str]) -> Result<Choice> {
+    loop {
+        print!("{}", question);
+        stdout().flush().map_err(|err| MemoireError::Io("stdout".to_owned(), err))?;
+        let mut answer = String::new();
+        let read = stdin().read_line(&mut answer).map_err(|err| MemoireError::Io("stdin".to_owned(), err))?;
+        if read == 0 {
+            println!();
+            return Ok(Choice::Other(others.last().copied().unwrap_or_default().to_owned()));
+        }
+        let answer = answer.trim().to_lowercase();
+        if let Ok(number) = answer.parse::<usize>() {
+            if number >= 1 && number <= count {
+                return Ok(Choice::Index(number - 1));
+            }
+        }
+        if others.contains(&answer.as_str()) {
+            return Ok(Choice::Other(answer));
+        }
+    }
+}
+
+
+/// Print the journal entries, most recent first
+fn print_log(store: &dyn Store, matches: &ArgMatches) -> Result<()> {
+    let limit = match matches.value_of("Limit") {
+        Some(limit) => limit.parse::<usize>().map_err(
+            |_err| MemoireError::InvalidArgument(format!("{} is not a valid number of entries", limit))
+        )?,
+        None => usize::MAX
+    };
+    let collection = matches.value_of("Collection");
+    let entries = store.log()?;
+    let entries = entries.iter().rev().filter(|entry| match collection {
+        Some(collection) => entry.get_collection() == collection,
+        None => true
+    }).take(limit);
+    for entry in entries {
+        let bookmark = entry.get_bookmark();
+        println!(
+            "#{}  {}  {}  {}  {}  {}  {}",
+            entry.get_id(),
+            format_time(Some(entry.get_time())),
+            entry.get_user(),
+            entry.get_operation(),
+            entry.get_collection(),
+            bookmark.get_id(),
+            bookmark.get_command()
         );
-    let matches = app.get_matches_from(inputs);
+        print_entry_changes(entry);
+    }
+    Ok(())
+}
+
+
+/// Print the fields changed by an edit or move, one per line
+fn print_entry_changes(entry: &JournalEntry) {
+    if let (Some(before), Some(after)) = (entry.get_before(), entry.get_after()) {
+        let fields = [
+            ("command", before.get_command().to_owned(), after.get_command().to_owned()),
+            ("annotation", before.get_annotation().to_owned(), after.get_annotation().to_owned()),
+            ("tags", before.get_tags_as_string(", "), after.get_tags_as_string(", ")),
+            ("collection", before.get_collection().to_owned(), after.get_collection().to_owned())
+        ];
+        for (name, before, after) in fields.iter().filter(|(_, before, after)| before != after) {
+            println!("    {}: {} -> {}", name, before, after);
+        }
+    }
+    if entry.get_operation() == Operation::Move {
+        if let (Some(from), Some(to)) = (entry.get_before_index(), entry.get_after_index()) {
+            println!("    index: {} -> {}", from, to);
+        }
+    }
+}
+
 
+fn parse_input(store: &dyn Store, config: &Config, matches: &ArgMatches) -> Result<Option<Vec<SearchResult>>> {
     // Check all non-search conditions first?
     // Check if input contains add sub command
     if let Some(matches) = matches.subcommand_matches("--add") {
@@ -198,82 +1099,144 @@ fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
         if matches.is_present("Tags") {
             tags = matches.values_of("Tags").unwrap().map(|s| s.to_string()).collect();
         }
-        let mut collection: String = "".to_string();
+        let mut collection: String = config.get_default_collection().to_string();
         if matches.is_present("Collection") {
             let vals: Vec<&str> = matches.values_of("Collection").unwrap().collect();
             collection = vals.join(" ");
         }
 
-        add(
-            &get_json_path(&collection),
-            &Bookmark::new(
-                &command,
-                &annotation,
-                &tags,
-                &collection
-            ),
-            None
-        );
-        // TODO: Fix this to only search keyword by collection
-        return search(
-            &get_collection_dir_path(),
-            &[&collection]
-        );
+        let bookmark = Bookmark::new(&command, &annotation, &tags, &collection);
+        // Added anyway, the user decides whether to run dedupe
+        for duplicate in find_duplicates(store, &bookmark)? {
+            eprintln!(
+                "Warning: bookmark {} in collection {} already runs this command",
+                duplicate.get_bookmark().get_id(),
+                duplicate.get_bookmark().get_collection()
+            );
+        }
+        let id = store.add(&bookmark, None)?;
+        println!("Added bookmark {}", id);
+        return store.list(&collection).map(Some);
     }
 
     // Look for delete subcommand
     if let Some(matches) = matches.subcommand_matches("--delete") {
-        let index: String = matches.values_of("Index").unwrap().collect();
-        let index: usize = index.parse().unwrap();
-        let collection: String = matches.values_of("Collection").unwrap().collect();
-        delete(&get_json_path(&collection), index);
-        // TODO: Fix this to only search keyword by collection
-        return search(
-            &get_collection_dir_path(),
-            &[&collection]
+        let result = find_by_id(store, matches)?;
+        store.delete(result.get_bookmark().get_id())?;
+        println!(
+            "Moved bookmark {0} to the trash, restore it with memoire trash restore {0}",
+            result.get_bookmark().get_id()
         );
+        return store.list(result.get_bookmark().get_collection()).map(Some);
+    }
+
+    // Look for move subcommand
+    if let Some(matches) = matches.subcommand_matches("--move") {
+        let result = find_by_id(store, matches)?;
+        let to = parse_index(matches, "To")?;
+        store.move_bookmark(result.get_bookmark().get_id(), to)?;
+        return store.list(result.get_bookmark().get_collection()).map(Some);
+    }
+
+    // Look for show subcommand
+    if let Some(matches) = matches.subcommand_matches("--show") {
+        print_bookmark(find_by_id(store, matches)?.get_bookmark());
+        return Ok(None);
+    }
+
+    // Look for export subcommands
+    if let Some(matches) = matches.subcommand_matches("export") {
+        run_export(store, matches)?;
+        return Ok(None);
+    }
+
+    // Look for collection subcommands
+    if let Some(matches) = matches.subcommand_matches("collection") {
+        parse_collection_input(store, matches)?;
+        return Ok(None);
+    }
+
+    // Look for trash subcommands
+    if let Some(matches) = matches.subcommand_matches("trash") {
+        parse_trash_input(store, matches)?;
+        return Ok(None);
+    }
+
+    // Look for log subcommand
+    if let Some(matches) = matches.subcommand_matches("log") {
+        print_log(store, matches)?;
+        return Ok(None);
+    }
+
+    // Look for dedupe subcommand
+    if let Some(matches) = matches.subcommand_matches("dedupe") {
+        run_dedupe(store, matches.is_present("Yes"))?;
+        return Ok(None);
+    }
+
+    // Look for revert subcommand
+    if let Some(matches) = matches.subcommand_matches("revert") {
+        let value = matches.value_of("Entry").unwrap();
+        let entry_id = value.trim_start_matches('#').parse::<u64>().map_err(
+            |_err| MemoireError::InvalidArgument(format!("{} is not a valid journal entry", value))
+        )?;
+        let entry = store.log()?.into_iter().find(|entry| entry.get_id() == entry_id).ok_or_else(
+            || MemoireError::InvalidArgument(format!("No journal entry #{}", entry_id))
+        )?;
+        println!("{}", revert(store, &entry)?);
+        return Ok(None);
+    }
+
+    // Look for import-legacy subcommand
+    if let Some(matches) = matches.subcommand_matches("--import-legacy") {
+        let history_path = match matches.value_of("File") {
+            Some(file) => file.to_owned(),
+            None => get_legacy_history_path()?
+        };
+        let count = import_legacy_history(store, &history_path, config.get_default_collection())?;
+        println!("Imported {} bookmarks from {}", count, history_path);
+        return Ok(None);
     }
 
     // Check if input contains edit command
-    // Currently doesn't support change collection
+    // Fields that are not given keep their current value, giving another
+    // collection moves the bookmark to the end of it
     if let Some(matches) = matches.subcommand_matches("--edit") {
-        let index: String = matches.values_of("Index").unwrap().collect();
-        let index: usize = index.parse().unwrap();
-        let mut command: String = "".to_string();
+        let result = find_by_id(store, matches)?;
+        let old = result.get_bookmark();
+        if matches.is_present("From") {
+            let vals: Vec<&str> = matches.values_of("From").unwrap().collect();
+            let from = vals.join(" ");
+            if old.get_collection() != &from {
+                return Err(MemoireError::InvalidArgument(format!(
+                    "Bookmark {} is in collection {}, not {}", old.get_id(), old.get_collection(), from
+                )));
+            }
+        }
+        let mut command: String = old.get_command().to_owned();
         if matches.is_present("Command") {
             let vals: Vec<&str> = matches.values_of("Command").unwrap().collect();
             command = vals.join(" ");
         }
-        let mut annotation: String = "".to_string();
+        let mut annotation: String = old.get_annotation().to_owned();
         if matches.is_present("Annotation") {
             let vals: Vec<&str> = matches.values_of("Annotation").unwrap().collect();
             annotation = vals.join(" ");
         }
-        let mut tags: Vec<String> = Vec::new();
+        let mut tags: Vec<String> = old.get_tags().to_owned();
         if matches.is_present("Tags") {
             tags = matches.values_of("Tags").unwrap().map(|s| s.to_string()).collect();
         }
-        let mut collection: String = "".to_string();
+        let mut collection: String = old.get_collection().to_owned();
         if matches.is_present("Collection") {
             let vals: Vec<&str> = matches.values_of("Collection").unwrap().collect();
             collection = vals.join(" ");
         }
-        delete(&get_json_path(&collection), index);
-        add(
-            &get_json_path(&collection),
-            &Bookmark::new(
-                &command,
-                &annotation,
-                &tags,
-                &collection
-            ),
-            Some(index)
-        );
-        // TODO: Fix this to only search keyword by collection
-        return search(
-            &get_collection_dir_path(),
-            &[&collection]
-        );
+        store.update(
+            old.get_id(),
+            &Bookmark::new(&command, &annotation, &tags, &collection)
+        )?;
+        return store.list(&collection).map(Some);
     }
 
     // Look for search args
@@ -286,15 +1249,14 @@ fn parse_input(inputs: Vec<String>) -> Vec<SearchResult> {
             );
         }
     }
-    if matches.is_present("Intersection") {
-        // search_mode.set_union(false);
-    }
+    let mode = if matches.is_present("Intersection") {
+        SearchMode::Intersection
+    } else {
+        config.get_search_mode()
+    };
     // Return all results if no args passed
     if keywords.is_empty() {
         keywords.push("");
     }
-    search(
-        &get_collection_dir_path(),
-        &keywords
-    )
+    store.search(&keywords, mode).map(Some)
 }
\ No newline at end of file
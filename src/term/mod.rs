@@ -3,6 +3,7 @@ mod event;
 
 use std::{
     io::{stdout, Stdout},
+    rc::Rc,
     sync::mpsc
 };
 
@@ -23,33 +24,41 @@ use tui::{
 
 use event::events;
 use widget::{
-    Action, WidgetManager, WidgetTrait, ACTIONS,
+    Action, WidgetManager, WidgetTrait,
     ACTION_LIST, INPUT_DIALOG, RESULT_TABLE, SEARCH_BAR
 };
 use crate::collection::{
     bookmark::Bookmark,
-    jq,
-    util::{get_collection_dir_path, get_json_path},
+    dedupe::find_duplicates,
+    store::{SearchResult, Store},
 };
+use crate::config::Config;
+use crate::error::{MemoireError, Result};
 
 
 pub struct Term {
     screen: Terminal<TermionBackend<AlternateScreen<RawTerminal<Stdout>>>>,
     events: mpsc::Receiver<Key>,
     wm: WidgetManager,
+    store: Rc<dyn Store>,
+    config: Rc<Config>,
 }
 
 
 impl Term {
-    pub fn new() -> Term {
-        Term {
+    pub fn new(store: Rc<dyn Store>, config: Rc<Config>) -> Result<Term> {
+        let mut wm = WidgetManager::new(Rc::clone(&store), Rc::clone(&config));
+        wm.reload_collection_metadata()?;
+        Ok(Term {
             screen: Terminal::new(TermionBackend::new(AlternateScreen::from(
-                stdout().into_raw_mode().unwrap(),
+                stdout().into_raw_mode().map_err(MemoireError::Terminal)?,
             )))
-            .unwrap(),
+            .map_err(MemoireError::Terminal)?,
             events: events(),
-            wm: WidgetManager::new(),
-        }
+            wm,
+            store,
+            config,
+        })
     }
 
     // FIXME: Dislike this approach. consider using setter or set when initialize
@@ -57,100 +66,143 @@ impl Term {
         &mut self.wm
     }
 
-    pub fn display(&mut self) -> Result<(), mpsc::RecvError> {
-        self.screen.hide_cursor().unwrap();
+    pub fn display(&mut self) -> Result<()> {
+        self.screen.hide_cursor().map_err(MemoireError::Terminal)?;
         loop {
-            self.draw();
+            self.draw()?;
 
             match self.events.recv()? {
                 Key::Ctrl('c') => break,
-                Key::Ctrl('a') => {
+                // Bookmarks can not be added to the trash
+                Key::Ctrl('a') if !self.wm.is_trash_view() => {
                     // NOTE: reset result table state, otherwise will edit instead of add
                     self.wm.reset_result_table_state();
                     if self.wm.get_cur_focus() != INPUT_DIALOG {
                         self.wm.set_input_dialog_inputs(
-                            Bookmark::default().to_vec()
+                            Bookmark::new("", "", &Vec::new(), self.config.get_default_collection()).to_vec()
                         );
                         self.wm.set_cur_focus(INPUT_DIALOG);
                     }
                 }
+                Key::Ctrl('s') => {
+                    self.wm.cycle_sort_key();
+                }
+                Key::Ctrl('t') => {
+                    self.wm.toggle_trash_view();
+                }
                 Key::Char('\n') => {
                     match self.wm.get_cur_focus() {
                         ACTION_LIST => {
-                            if let Some(action_index) = self.wm.get_action_list_state_selected() {
-                                match ACTIONS[action_index] {
+                            if self.wm.is_selected_action_disabled() {
+                                self.wm.set_notice(Some(format!(
+                                    "Collection {} is read-only", self.wm.get_selected_item_collection()
+                                )));
+                                self.wm.reset_action_list_state();
+                                self.wm.set_cur_focus(RESULT_TABLE);
+                                continue;
+                            }
+                            if let Some(action) = self.wm.get_selected_action() {
+                                match action {
                                     Action::Copy => {
-                                        let mut clipboard = Clipboard::new().unwrap();
+                                        if let Some(id) = self.wm.get_selected_item_id() {
+                                            self.store.mark_used(id)?;
+                                        }
+                                        let mut clipboard = Clipboard::new()?;
                                         clipboard.set_text(
                                             self.wm
                                                 .get_selected_item_command()
                                                 .to_owned()
-                                        )
-                                        .unwrap();
-                                        break;
+                                        )?;
+                                        if self.config.get_copy_then_quit() {
+                                            break;
+                                        }
+                                        self.wm.reset_action_list_state();
+                                        self.wm.set_cur_focus(RESULT_TABLE);
                                     }
                                     Action::Edit => {
                                         self.wm.update_input_dialog_from_result_table();
                                         self.wm.set_cur_focus(INPUT_DIALOG);
                                     }
                                     Action::Delete => {
-                                        match self.wm.get_selected_item_index() {
-                                            Some(index) => {
-                                                jq::delete(
-                                                    &get_json_path(self.wm.get_selected_item_collection()),
-                                                    index
-                                                );
-                                                self.wm.update_result_table(jq::search(
-                                                    &get_collection_dir_path(),
-                                                    &[self.wm.get_selected_item_collection()]
-                                                ))
-                                            },
-                                            None => {}  // Add error log
+                                        if let Some(id) = self.wm.get_selected_item_id() {
+                                            self.store.delete(id)?;
+                                            let results = self.store.list(
+                                                self.wm.get_selected_item_collection()
+                                            )?;
+                                            self.wm.update_result_table(results)
                                         }
                                         self.wm.reset_action_list_state();
                                         self.wm.reset_result_table_state();
                                         
                                         self.wm.set_cur_focus(RESULT_TABLE);
                                     }
+                                    Action::MoveUp | Action::MoveDown => {
+                                        if let Some(index) = self.wm.get_selected_item_index() {
+                                            let to = match action {
+                                                Action::MoveUp => index.checked_sub(1),
+                                                _ => Some(index + 1)
+                                            };
+                                            if let Some(to) = to {
+                                                let collection = self.wm.get_selected_item_collection().to_owned();
+                                                let to = self.store.move_bookmark(
+                                                    self.wm.get_selected_item_id().unwrap_or_default(),
+                                                    to
+                                                )?;
+                                                let results = self.store.list(&collection)?;
+                                                self.wm.update_result_table(results);
+                                                self.wm.select_result_table_item(&collection, to);
+                                            }
+                                        }
+                                    }
+                                    Action::Restore => {
+                                        if let Some(id) = self.wm.get_selected_item_id() {
+                                            self.store.restore(id)?;
+                                        }
+                                        self.wm.reset_action_list_state();
+                                        self.wm.reload_trash();
+                                        self.wm.set_cur_focus(RESULT_TABLE);
+                                    }
                                 }
                                 
                             }
                         }
-                        RESULT_TABLE => if self.wm.get_result_table().get_state().selected().is_some() {
+                        RESULT_TABLE if self.wm.get_result_table().get_state().selected().is_some() => {
+                            self.wm.update_disabled_actions()?;
                             self.wm.set_cur_focus(ACTION_LIST);
                         },
                         INPUT_DIALOG => {
                             let bookmark = dialog_inputs_to_bookmark(
-                                self.wm.get_input_dialog().get_inputs_as_strings()
+                                self.wm.get_input_dialog().get_inputs_as_strings(),
+                                self.config.get_default_collection()
                             );
-                            match self.wm.get_selected_item_index() {
-                                Some(index) => {  // Edit
-                                    jq::delete(
-                                        &get_json_path(self.wm.get_selected_item_collection()),
-                                        index
-                                    );
-                                    jq::add(
-                                        &get_json_path(bookmark.get_collection()),
-                                        &bookmark,
-                                        Some(index)
-                                    );
+                            let saved = match self.wm.get_selected_item_id() {
+                                Some(id) => {  // Edit
+                                    self.store.update(id, &bookmark)
                                 },
                                 None => {  // Add
-                                    jq::add(
-                                        &get_json_path(bookmark.get_collection()),
-                                        &bookmark,
-                                        None
-                                    );
+                                    let duplicates = find_duplicates(self.store.as_ref(), &bookmark)?;
+                                    let added = self.store.add(&bookmark, None);
+                                    if added.is_ok() {
+                                        self.wm.set_notice(duplicates.first().map(|duplicate| format!(
+                                            "Bookmark {} in collection {} already runs this command, run memoire dedupe to merge them",
+                                            duplicate.get_bookmark().get_id(),
+                                            duplicate.get_bookmark().get_collection()
+                                        )));
+                                    }
+                                    added.map(|_| ())
                                 }
                             };
+                            // Saving into a read-only collection is refused, not fatal
+                            match saved {
+                                Err(MemoireError::ReadOnly(collection)) => self.wm.set_notice(Some(
+                                    format!("Collection {} is read-only, the bookmark was not saved", collection)
+                                )),
+                                saved => saved?
+                            };
                             self.wm.reset_action_list_state();
                             self.wm.reset_result_table_state();
                             self.wm.update_result_table(
-                                // update this to search by only tag
-                                jq::search(
-                                    &get_collection_dir_path(),
-                                    &[bookmark.get_collection()]
-                                )
+                                self.store.list(bookmark.get_collection())?
                             );
                             self.wm.set_cur_focus(RESULT_TABLE);
                         },
@@ -187,12 +239,99 @@ impl Term {
                 }
                 _ => {}
             }
+            // Surface errors raised while widgets handled the key
+            if let Some(err) = self.wm.take_error() {
+                return Err(err);
+            }
         }
 
         Ok(())
     }
 
-    fn draw(&mut self) {
+    /// Let the user mark some of candidates and give them an annotation, tags
+    /// and a collection in bulk
+    ///
+    /// Returns the marked bookmarks with these set, None if the user quit
+    ///
+    /// # Arguments
+    ///
+    /// * `candidates` - The bookmarks to pick from, with distinct indexes
+    /// * `template` - A bookmark holding the annotation, tags and collection suggested
+    pub fn pick(&mut self, candidates: Vec<SearchResult>, template: &Bookmark) -> Result<Option<Vec<Bookmark>>> {
+        self.screen.hide_cursor().map_err(MemoireError::Terminal)?;
+        self.wm.start_picking(candidates);
+        loop {
+            self.draw()?;
+
+            match self.events.recv()? {
+                Key::Ctrl('c') => return Ok(None),
+                Key::Char(' ') if self.wm.get_cur_focus() == RESULT_TABLE => {
+                    self.wm.toggle_mark();
+                }
+                Key::Char('\n') => {
+                    match self.wm.get_cur_focus() {
+                        RESULT_TABLE if self.wm.get_result_table().get_state().selected().is_some() => {
+                            if self.wm.get_picked().is_empty() {
+                                self.wm.toggle_mark();
+                            }
+                            self.wm.set_input_dialog_inputs(template.to_vec().split_off(1));
+                            self.wm.set_cur_focus(INPUT_DIALOG);
+                        },
+                        INPUT_DIALOG => {
+                            let inputs = self.wm.get_input_dialog().get_inputs_as_strings();
+                            return Ok(Some(
+                                self.wm.get_picked().iter().map(|picked| dialog_inputs_to_bookmark(
+                                    vec![
+                                        picked.get_bookmark().get_command().to_owned(),
+                                        inputs[0].to_owned(),
+                                        inputs[1].to_owned(),
+                                        inputs[2].to_owned()
+                                    ],
+                                    self.config.get_default_collection()
+                                )).collect()
+                            ));
+                        },
+                        SEARCH_BAR => {
+                            if self.wm.get_result_table().get_state().selected().is_none() {
+                                self.wm.key_down();
+                            } else {
+                                self.wm.set_cur_focus(RESULT_TABLE);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Key::Char(character) => {
+                    self.wm.key_char(character);
+                }
+                Key::Up => {
+                    self.wm.key_up();
+                }
+                Key::Down => {
+                    self.wm.key_down();
+                }
+                Key::Left => {
+                    self.wm.key_left();
+                }
+                Key::Right => {
+                    self.wm.key_right();
+                }
+                Key::Backspace => {
+                    self.wm.key_backspace();
+                }
+                Key::Esc => {
+                    self.wm.key_esc();
+                }
+                _ => {}
+            }
+            if let Some(err) = self.wm.take_error() {
+                return Err(err);
+            }
+        }
+    }
+
+    fn draw(&mut self) -> Result<()> {
+        let colors = *self.config.get_colors();
         let cur_focus = self.wm.get_cur_focus();
         // For render input dialog
         let num_of_inputs = self.wm.get_input_dialog().get_inputs_size();
@@ -247,12 +386,12 @@ impl Term {
                                 // FIXME: Must be a cleaner way
                                 if let Some(cur_input_index) = cur_focus_input {
                                     if i == cur_input_index {
-                                        Style::default().fg(Color::LightYellow)
+                                        Style::default().fg(colors.get_selected())
                                     } else {
-                                        Style::default().fg(Color::White)
+                                        Style::default().fg(colors.get_text())
                                     }
                                 } else {
-                                    Style::default().fg(Color::White)
+                                    Style::default().fg(colors.get_text())
                                 }
                             ).wrap(Wrap { trim: false, break_word: true }),
                             inner_layout[i]
@@ -283,23 +422,23 @@ impl Term {
                     f.render_stateful_widget(action_list_widget, windows_layout2[1], &mut action_list_state.clone());
                 }
             }
-        ).unwrap();
+        ).map_err(MemoireError::Terminal)?;
+        Ok(())
     }
 }
 
-fn dialog_inputs_to_bookmark(inputs: Vec<String>) -> Bookmark {
+fn dialog_inputs_to_bookmark(inputs: Vec<String>, default_collection: &str) -> Bookmark {
     Bookmark::new(
-        &replace_special_chars(&inputs[0]), 
-        &replace_special_chars(&inputs[1]),
+        &inputs[0],
+        &inputs[1],
         &inputs[2].split(',').filter_map(|s| if "".eq(s.trim()) {
             None
         } else {
-            Some(replace_special_chars(s).trim().to_owned())
+            Some(s.trim().to_owned())
         }).collect(),
-        &replace_special_chars(&inputs[3])
+        match inputs[3].trim() {
+            "" => default_collection,
+            collection => collection
+        }
     )
-}
-
-fn replace_special_chars(s: &str) -> String {
-    s.replace('\\', "\\\\").replace('"', "\\\"")
 }
\ No newline at end of file
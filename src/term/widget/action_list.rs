@@ -10,11 +10,14 @@ use tui::{
 use super::widget_trait::WidgetTrait;
 
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Action {
     Copy,
     Edit,
-    Delete
+    Delete,
+    MoveUp,
+    MoveDown,
+    Restore
 }
 
 impl Display for Action {
@@ -23,11 +26,24 @@ impl Display for Action {
     }
 }
 
-pub const ACTIONS: [Action; 3] = [Action::Copy, Action::Edit, Action::Delete];
+pub const ACTIONS: [Action; 5] = [
+    Action::Copy, Action::Edit, Action::Delete, Action::MoveUp, Action::MoveDown
+];
+
+// Actions on the bookmarks of the trash view
+pub const TRASH_ACTIONS: [Action; 1] = [Action::Restore];
+
+// Actions changing the collection, unavailable in read-only ones
+pub const WRITE_ACTIONS: [Action; 5] = [
+    Action::Edit, Action::Delete, Action::MoveUp, Action::MoveDown, Action::Restore
+];
 
 pub struct ActionList {
     state: ListState,
-    items: Vec<String>
+    actions: Vec<Action>,
+    items: Vec<String>,
+    // Listed but greyed out, selecting them does nothing
+    disabled: Vec<Action>
 }
 
 
@@ -71,18 +87,43 @@ impl ActionList {
             state: ListState::default(),
             items: actions.iter().map(
                 |i| i.to_string()
-            ).collect()
+            ).collect(),
+            actions,
+            disabled: Vec::new()
         }
     }
 
-    pub fn get_widget(&self) -> List {
+    /// Replace the actions listed, clearing the selection
+    pub fn set_actions(&mut self, actions: Vec<Action>) {
+        self.items = actions.iter().map(|action| action.to_string()).collect();
+        self.actions = actions;
+        self.disabled.clear();
+        self.reset();
+    }
+
+    /// Grey out the actions given, enabling every other one
+    pub fn set_disabled(&mut self, disabled: Vec<Action>) {
+        self.disabled = disabled;
+    }
+
+    pub fn is_disabled(&self, action: &Action) -> bool {
+        self.disabled.contains(action)
+    }
+
+    pub fn get_selected_action(&self) -> Option<&Action> {
+        self.state.selected().map(|ind| &self.actions[ind])
+    }
+
+    pub fn get_widget(&self) -> List<'_> {
         // Define selected action style
         let selected_action_style = Style::default().fg(Color::Black).bg(Color::White).add_modifier(Modifier::BOLD);
 
         // Set list
-        let action_options: Vec<ListItem> = self.items.iter().map(
+        let action_options: Vec<ListItem> = self.items.iter().zip(self.actions.iter()).map(
                 // Note here style is applied on ListItem instead of Span
-                |i| ListItem::new(Span::raw(i)).style(Style::default().fg(Color::White))
+                |(i, action)| ListItem::new(Span::raw(i)).style(Style::default().fg(
+                    if self.is_disabled(action) { Color::DarkGray } else { Color::White }
+                ))
             ).collect();
         List::new(action_options)
             .block(Block::default().borders(Borders::ALL).title("Actions"))
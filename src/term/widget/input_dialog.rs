@@ -84,7 +84,7 @@ impl Input {
         self
     }
 
-    fn get_text(&self) -> Spans {
+    fn get_text(&self) -> Spans<'_> {
         let mut spans: Vec<Span> = Vec::new();
         if let Some(span) = &self.prefix {
             spans.push(span.clone());
@@ -121,7 +121,7 @@ impl Input {
         Spans::from(spans)
     }
 
-    pub fn get_widget(&self) -> Paragraph {
+    pub fn get_widget(&self) -> Paragraph<'_> {
         Paragraph::new(
             self.get_text()
         )
@@ -212,8 +212,8 @@ impl InputGroup {
         }
     }
 
-    pub fn get_widgets(&self) -> Vec<Paragraph> {
-        (&self.inputs).iter().map(|input| input.get_widget()).collect()        
+    pub fn get_widgets(&self) -> Vec<Paragraph<'_>> {
+        self.inputs.iter().map(|input| input.get_widget()).collect()        
     }
 
     pub fn get_inputs_size(&self) -> usize {
@@ -225,11 +225,11 @@ impl InputGroup {
     }
 
     pub fn get_inputs_as_strings(&self) -> Vec<String> {
-        (&self.inputs).iter().map(|input| input.get_input().to_string()).collect()
+        self.inputs.iter().map(|input| input.get_input().to_string()).collect()
     }
 
     pub fn get_inputs_names(&self) -> Vec<String> {
-        (&self.inputs).iter().map(|input| input.get_name().to_string()).collect()
+        self.inputs.iter().map(|input| input.get_name().to_string()).collect()
     }
 
     fn update_input_focus(&mut self) {
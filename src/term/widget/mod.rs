@@ -3,7 +3,7 @@ mod input_dialog;
 mod result_table;
 mod widget_trait;
 
-use std::collections::HashMap;
+use std::{collections::{HashMap, HashSet}, rc::Rc};
 
 use tui::{
     style::{Color, Modifier, Style},
@@ -11,14 +11,20 @@ use tui::{
     widgets::{Block, Borders, Paragraph, Wrap}
 };
 
-use crate::collection::bookmark::Bookmark;
-use crate::collection::jq::{SearchResult, search};
-use crate::collection::util::get_collection_dir_path;
+use crate::collection::{
+    bookmark::Bookmark,
+    metadata::{CollectionMetadata, load_all},
+    util::format_time
+};
+use crate::collection::store::{SearchMode, SearchResult, SortKey, Store, TrashedBookmark};
+use crate::config::Config;
+use crate::error::{MemoireError, Result};
 use action_list::ActionList;
 use input_dialog::{Input, InputGroup};
 use result_table::ResultTable;
 pub use action_list::Action;
 pub use action_list::ACTIONS;
+use action_list::{TRASH_ACTIONS, WRITE_ACTIONS};
 pub use widget_trait::WidgetTrait;
 
 
@@ -53,7 +59,13 @@ impl WidgetTrait for Widget {
 
 pub struct WidgetManager {
     widgets: HashMap<String, Widget>,
-    cur_focus: String  // current on focus widget
+    cur_focus: String,  // current on focus widget
+    error: Option<MemoireError>,  // error raised while handling a key
+    trash: Option<Vec<TrashedBookmark>>,  // trashed bookmarks while the trash is shown
+    notice: Option<String>,  // warning about the last change, shown with the hints
+    candidates: Option<Vec<SearchResult>>,  // bookmarks to pick from, while picking
+    store: Rc<dyn Store>,
+    config: Rc<Config>
 }
 
 
@@ -164,7 +176,7 @@ impl WidgetTrait for WidgetManager {
 
 
 impl WidgetManager {
-    pub fn new() -> WidgetManager {
+    pub fn new(store: Rc<dyn Store>, config: Rc<Config>) -> WidgetManager {
         let mut widgets: HashMap<String, Widget> = HashMap::new();
         widgets.insert(
             ACTION_LIST.to_string(),
@@ -194,18 +206,104 @@ impl WidgetManager {
                 InputGroup::new(vec!["Command", "Annotation", "Tags", "Collection"])
             )
         );
-        widgets.insert(RESULT_TABLE.to_string(), Widget::ResultTable(ResultTable::default()));
+        widgets.insert(RESULT_TABLE.to_string(), Widget::ResultTable(ResultTable::new(HashSet::new(), Rc::clone(&config))));
         WidgetManager {
             widgets,
-            cur_focus: RESULT_TABLE.to_string()
+            cur_focus: RESULT_TABLE.to_string(),
+            error: None,
+            trash: None,
+            notice: None,
+            candidates: None,
+            store,
+            config
         }
     }
 
+    /// Read the metadata of every collection, used by result_table
+    pub fn reload_collection_metadata(&mut self) -> Result<()> {
+        let metadata = load_all(self.store.as_ref())?;
+        self.get_mut_result_table().set_collection_metadata(metadata);
+        Ok(())
+    }
+
     /// Update result_table with passed input
     pub fn update_result_table(&mut self, results: Vec<SearchResult>) {
         self.get_mut_result_table().update_results(results);
     }
 
+    /// Set the attribute result_table is sorted by, None keeps the order of
+    /// the results
+    pub fn set_sort_key(&mut self, sort_key: Option<SortKey>) {
+        self.get_mut_result_table().set_sort_key(sort_key);
+    }
+
+    /// Sort result_table by the next attribute, back to unsorted after the last
+    pub fn cycle_sort_key(&mut self) {
+        let sort_key = match self.get_result_table().get_sort_key() {
+            Some(sort_key) => sort_key.next(),
+            None => Some(SortKey::Created)
+        };
+        self.reset_result_table_state();
+        self.set_sort_key(sort_key);
+    }
+
+    /// Switch result_table between the search results and the trash
+    pub fn toggle_trash_view(&mut self) {
+        self.get_mut_action_list().reset();
+        if self.trash.take().is_some() {
+            self.get_mut_action_list().set_actions(ACTIONS.to_vec());
+            self.get_mut_result_table().set_title("Results");
+            self.update_result_table_from_search_bar();
+        } else {
+            self.get_mut_action_list().set_actions(TRASH_ACTIONS.to_vec());
+            self.get_mut_result_table().set_title("Trash");
+            self.reload_trash();
+        }
+        self.set_cur_focus(RESULT_TABLE);
+    }
+
+    /// Show candidates instead of the search results, to mark some of them
+    /// and give them an annotation, tags and collection in bulk
+    pub fn start_picking(&mut self, candidates: Vec<SearchResult>) {
+        self.candidates = Some(candidates);
+        self.get_mut_action_list().set_actions(Vec::new());
+        self.widgets.insert(
+            INPUT_DIALOG.to_string(),
+            Widget::InputDialog(InputGroup::new(vec!["Annotation", "Tags", "Collection"]))
+        );
+        let result_table = self.get_mut_result_table();
+        result_table.enable_marks();
+        result_table.set_title("Commands");
+        self.update_result_table_from_search_bar();
+        self.set_cur_focus(RESULT_TABLE);
+    }
+
+    /// Mark the selected candidate, or unmark it if it is marked
+    pub fn toggle_mark(&mut self) {
+        self.get_mut_result_table().toggle_mark();
+    }
+
+    /// Returns the marked candidates, in their order
+    pub fn get_picked(&self) -> Vec<&SearchResult> {
+        let marked = self.get_result_table().get_marked();
+        self.candidates.iter().flatten().filter(|candidate| marked.contains(&candidate.get_index())).collect()
+    }
+
+    pub fn is_trash_view(&self) -> bool {
+        self.trash.is_some()
+    }
+
+    /// Read the trash again after it changed, filtered by the search bar
+    pub fn reload_trash(&mut self) {
+        match self.store.list_trash() {
+            Ok(trash) => {
+                self.trash = Some(trash);
+                self.update_result_table_from_search_bar();
+            },
+            Err(err) => self.error = Some(err)
+        }
+    }
+
     /// Reset the state of result_table
     pub fn reset_result_table_state(&mut self) {
         self.get_mut_result_table().reset_state();
@@ -243,6 +341,11 @@ impl WidgetManager {
         }
     }
 
+    /// Select the result_table row holding the bookmark at index of collection
+    pub fn select_result_table_item(&mut self, collection: &str, index: usize) {
+        self.get_mut_result_table().select_item(collection, index);
+    }
+
     pub fn get_selected_item_index(&self) -> Option<usize> {
         let result_table = self.get_result_table();
         result_table.get_state()
@@ -253,6 +356,18 @@ impl WidgetManager {
                     )
     }
 
+    pub fn get_selected_item_id(&self) -> Option<&str> {
+        let result_table = self.get_result_table();
+        result_table.get_state()
+                    .selected()
+                    .map(
+                        |state| result_table.get_item(state)
+                                            .get_bookmark()
+                                            .get_id()
+                                            .as_str()
+                    )
+    }
+
     pub fn get_selected_item_collection(&self) -> &str {
         let result_table = self.get_result_table();
         result_table.get_item(result_table.get_state().selected().unwrap()).get_bookmark().get_collection()
@@ -285,8 +400,27 @@ impl WidgetManager {
         }
     }
 
-    pub fn get_action_list_state_selected(&self) -> Option<usize> {
-        self.get_action_list().get_state().selected()
+    pub fn get_selected_action(&self) -> Option<Action> {
+        self.get_action_list().get_selected_action().cloned()
+    }
+
+    /// Grey out the actions that would change the selected item if its
+    /// collection is read-only
+    pub fn update_disabled_actions(&mut self) -> Result<()> {
+        let read_only = self.store.is_read_only(self.get_selected_item_collection())?;
+        self.get_mut_action_list().set_disabled(
+            if read_only { WRITE_ACTIONS.to_vec() } else { Vec::new() }
+        );
+        Ok(())
+    }
+
+    /// Returns true if the selected action is greyed out
+    pub fn is_selected_action_disabled(&self) -> bool {
+        let action_list = self.get_action_list();
+        match action_list.get_selected_action() {
+            Some(action) => action_list.is_disabled(action),
+            None => false
+        }
     }
 
     pub fn reset_action_list_state(&mut self) {
@@ -319,35 +453,111 @@ impl WidgetManager {
         self.get_mut_input_dialog().set_inputs(inputs);
     }
 
-    pub fn get_display_panel_widget(&self) -> Paragraph {
+    pub fn get_display_panel_widget(&self) -> Paragraph<'_> {
+        if self.candidates.is_some() {
+            return self.get_picking_hints_widget();
+        }
         let display_panel: Paragraph = match self.get_result_table().get_state().selected() {
             Some(result_table_state) => {
                 let result_table = self.get_result_table();
-                Paragraph::new(
-                    bookmark_to_spans(
-                        result_table.get_item(result_table_state).get_bookmark()
-                    )
-                )
+                let bookmark = result_table.get_item(result_table_state).get_bookmark();
+                let mut spans = bookmark_to_spans(bookmark);
+                if let Some(metadata) = result_table.get_collection_metadata(bookmark.get_collection()) {
+                    spans.extend(metadata_to_spans(metadata));
+                }
+                if let Some(notice) = &self.notice {
+                    spans.push(Spans::from(vec![Span::styled(notice.to_owned(), Style::default().fg(Color::LightYellow))]));
+                }
+                let deleted_at = self.trash.iter().flatten().find(
+                    |trashed| trashed.get_bookmark().get_id() == bookmark.get_id()
+                ).map(|trashed| trashed.get_deleted_at());
+                if let Some(deleted_at) = deleted_at {
+                    spans.push(Spans::from(vec![
+                        Span::styled("Deleted: ", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)),
+                        Span::styled(format_time(Some(deleted_at)), Style::default().fg(Color::LightRed))
+                    ]));
+                }
+                Paragraph::new(spans)
             },
             None => {
-                Paragraph::new(
-                    vec![
-                        Spans::from(vec![Span::styled("Hints*", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))]),
-                        Spans::from(vec![
-                            Span::styled("Ctrl-c", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)),
-                            Span::styled(" to quit anytime", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
-                        ]),
-                        Spans::from(vec![
-                            Span::styled("Ctrl-a", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
-                            Span::styled(" to add", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
-                        ])
-                    ]
-                )
+                let mut spans = vec![
+                    Spans::from(vec![Span::styled("Hints*", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))]),
+                    Spans::from(vec![
+                        Span::styled("Ctrl-c", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)),
+                        Span::styled(" to quit anytime", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
+                    ]),
+                    Spans::from(vec![
+                        Span::styled("Ctrl-a", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                        Span::styled(" to add", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
+                    ]),
+                    Spans::from(vec![
+                        Span::styled("Ctrl-s", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                        Span::styled(" to sort by created, updated, used or use count", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
+                    ]),
+                    Spans::from(vec![
+                        Span::styled("Ctrl-t", Style::default().fg(Color::LightBlue).add_modifier(Modifier::BOLD)),
+                        Span::styled(
+                            if self.is_trash_view() {
+                                " to go back to the results"
+                            } else {
+                                " to browse deleted bookmarks and restore them"
+                            },
+                            Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)
+                        )
+                    ])
+                ];
+                if let Some(notice) = &self.notice {
+                    spans.push(Spans::from(vec![Span::styled("Notice*", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))]));
+                    spans.push(Spans::from(vec![Span::styled(notice.to_owned(), Style::default().fg(Color::LightYellow))]));
+                }
+                let broken: Vec<&str> = self.store.get_broken_collections().iter().map(
+                    |collection| collection.get_name()
+                ).collect();
+                if !broken.is_empty() {
+                    spans.push(Spans::from(vec![Span::styled("Warning*", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD))]));
+                    spans.push(Spans::from(vec![
+                        Span::styled(
+                            format!("Skipped broken collections: {}, see the errors in the terminal after quitting", broken.join(", ")),
+                            Style::default().fg(Color::LightRed)
+                        )
+                    ]));
+                }
+                if let Some(err) = self.store.get_trash_error() {
+                    spans.push(Spans::from(vec![Span::styled("Warning*", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD))]));
+                    spans.push(Spans::from(vec![Span::styled(err.to_string(), Style::default().fg(Color::LightRed))]));
+                }
+                Paragraph::new(spans)
             }
         };
         display_panel.block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true, break_word: false })
     }
 
+    fn get_picking_hints_widget(&self) -> Paragraph<'_> {
+        let spans = vec![
+            Spans::from(vec![Span::styled("Hints*", Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD))]),
+            Spans::from(vec![
+                Span::styled("Ctrl-c", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)),
+                Span::styled(" to quit without importing", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
+            ]),
+            Spans::from(vec![
+                Span::styled("Space", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+                Span::styled(" to mark or unmark the selected command", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD))
+            ]),
+            Spans::from(vec![
+                Span::styled("Enter", Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD)),
+                Span::styled(
+                    " to annotate, tag and import the marked commands, the selected one if none is marked",
+                    Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)
+                )
+            ]),
+            Spans::from(vec![
+                Span::styled("Marked: ", Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD)),
+                Span::styled(self.get_picked().len().to_string(), Style::default().fg(Color::LightGreen))
+            ])
+        ];
+        Paragraph::new(spans).block(Block::default().borders(Borders::ALL)).wrap(Wrap { trim: true, break_word: false })
+    }
+
     pub fn get_search_bar(&self) -> &Input {
         match self.widgets.get(SEARCH_BAR).unwrap() {
             Widget::SearchBar(input) => {
@@ -373,21 +583,73 @@ impl WidgetManager {
         &self.cur_focus
     }
 
+    pub fn set_notice(&mut self, notice: Option<String>) {
+        self.notice = notice;
+    }
+
+    /// Takes the error raised while handling the last key, if any
+    pub fn take_error(&mut self) -> Option<MemoireError> {
+        self.error.take()
+    }
+
     fn update_result_table_from_search_bar(&mut self) {
         self.get_mut_result_table().reset_state();
         let keywords = self.get_search_bar().get_input().to_string();
-        self.get_mut_result_table().update_results(
-            search(
-                &get_collection_dir_path(),
-                &keywords.trim().split(' ').collect::<Vec<&str>>()
-            )
-        );
+        let keywords: Vec<&str> = keywords.trim().split(' ').collect();
+        if let Some(candidates) = &self.candidates {
+            let results = candidates.iter().filter(|candidate| match self.config.get_search_mode() {
+                SearchMode::Union => keywords.iter().any(|keyword| candidate.get_bookmark().contains(keyword)),
+                SearchMode::Intersection => keywords.iter().all(|keyword| candidate.get_bookmark().contains(keyword))
+            }).cloned().collect();
+            self.get_mut_result_table().update_results(results);
+            return;
+        }
+        if let Some(trash) = &self.trash {
+            let results = trash.iter().filter(|trashed| match self.config.get_search_mode() {
+                SearchMode::Union => keywords.iter().any(|keyword| trashed.get_bookmark().contains(keyword)),
+                SearchMode::Intersection => keywords.iter().all(|keyword| trashed.get_bookmark().contains(keyword))
+            }).map(
+                |trashed| SearchResult::new(trashed.get_index(), trashed.get_bookmark().to_owned())
+            ).collect();
+            self.get_mut_result_table().update_results(results);
+            return;
+        }
+        match self.store.search(
+            &keywords,
+            self.config.get_search_mode()
+        ) {
+            Ok(results) => self.get_mut_result_table().update_results(results),
+            Err(err) => self.error = Some(err)
+        }
     }
 }
 
 
-fn bookmark_to_spans(bookmark: &Bookmark) -> Vec<Spans> {
+/// Returns the lines showing the description and owner of a collection
+fn metadata_to_spans(metadata: &CollectionMetadata) -> Vec<Spans<'_>> {
+    let mut spans = Vec::new();
+    if !metadata.get_description().is_empty() {
+        spans.push(Spans::from(vec![
+            Span::styled("Description: ", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+            Span::styled(metadata.get_description(), Style::default().fg(Color::LightMagenta))
+        ]));
+    }
+    if !metadata.get_owner().is_empty() {
+        spans.push(Spans::from(vec![
+            Span::styled("Owner: ", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
+            Span::styled(metadata.get_owner(), Style::default().fg(Color::LightMagenta))
+        ]));
+    }
+    spans
+}
+
+
+fn bookmark_to_spans(bookmark: &Bookmark) -> Vec<Spans<'_>> {
     vec![
+        Spans::from(vec![
+            Span::styled("Id: ", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+            Span::styled(bookmark.get_id(), Style::default().fg(Color::Gray))
+        ]),
         Spans::from(vec![
             Span::styled("Command: ", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD)),
             Span::styled(bookmark.get_command(), Style::default().fg(Color::LightRed))
@@ -404,5 +666,17 @@ fn bookmark_to_spans(bookmark: &Bookmark) -> Vec<Spans> {
             Span::styled("Collection: ", Style::default().fg(Color::LightMagenta).add_modifier(Modifier::BOLD)),
             Span::styled(bookmark.get_collection(), Style::default().fg(Color::LightMagenta))
         ]),
+        Spans::from(vec![
+            Span::styled("Created: ", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+            Span::styled(format_time(bookmark.get_created_at()), Style::default().fg(Color::Gray)),
+            Span::styled("  Updated: ", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+            Span::styled(format_time(bookmark.get_updated_at()), Style::default().fg(Color::Gray))
+        ]),
+        Spans::from(vec![
+            Span::styled("Last used: ", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+            Span::styled(format_time(bookmark.get_last_used_at()), Style::default().fg(Color::Gray)),
+            Span::styled("  Uses: ", Style::default().fg(Color::Gray).add_modifier(Modifier::BOLD)),
+            Span::styled(bookmark.get_use_count().to_string(), Style::default().fg(Color::Gray))
+        ]),
     ]
 }
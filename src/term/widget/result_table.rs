@@ -1,18 +1,27 @@
-use std::collections::HashSet;
+use std::{collections::{HashMap, HashSet}, rc::Rc};
 
 use tui::{
     layout::Constraint,
-    style::{Color, Modifier, Style},
-    widgets::{Block, Borders, Row, Table, TableState}
+    style::{Modifier, Style},
+    widgets::{Block, Borders, Cell, Row, Table, TableState}
 };
 
-use crate::collection::jq::SearchResult;
+use crate::collection::metadata::CollectionMetadata;
+use crate::collection::store::{SearchResult, SortKey, sort_results};
+use crate::config::{Config, parse_color};
 use super::widget_trait::WidgetTrait;
 
 
 pub struct ResultTable {
     state: TableState,
     items: Vec<SearchResult>,
+    unsorted_items: Vec<SearchResult>,  // items in the order they were given
+    sort_key: Option<SortKey>,
+    title: String,
+    widths: [Constraint; 5],  // widths of the columns, from config
+    metadata: HashMap<String, CollectionMetadata>,  // by collection name
+    marked: Option<HashSet<usize>>,  // indexes of the marked items, None unless items can be marked
+    config: Rc<Config>,
 }
 
 impl WidgetTrait for ResultTable {
@@ -41,50 +50,134 @@ impl WidgetTrait for ResultTable {
 }
 
 impl ResultTable {
-    pub fn new(results: HashSet<SearchResult>) -> ResultTable {
+    pub fn new(results: HashSet<SearchResult>, config: Rc<Config>) -> ResultTable {
+        let items = hashset_to_vec(&results);
+        let columns = config.get_columns();
         ResultTable {
             state: TableState::default(),
-            items: hashset_to_vec(&results)
+            unsorted_items: items.to_owned(),
+            items,
+            sort_key: None,
+            title: "Results".to_owned(),
+            widths: [
+                Constraint::Length(8),
+                Constraint::Percentage(columns.get_command()),
+                Constraint::Percentage(columns.get_annotation()),
+                Constraint::Percentage(columns.get_tags()),
+                Constraint::Percentage(columns.get_collection()),
+            ],
+            metadata: HashMap::new(),
+            marked: None,
+            config
         }
     }
 
-    pub fn default() -> ResultTable {
-        ResultTable::new(HashSet::new())
+    pub fn update_results(&mut self, results: Vec<SearchResult>) {
+        self.unsorted_items = results;
+        self.sort_items();
     }
 
-    pub fn update_results(&mut self, results: Vec<SearchResult>) {
-        self.items = results;
+    /// Set the metadata used to rank, color and label collections
+    pub fn set_collection_metadata(&mut self, metadata: HashMap<String, CollectionMetadata>) {
+        self.metadata = metadata;
+        self.sort_items();
+    }
+
+    pub fn get_collection_metadata(&self, collection: &str) -> Option<&CollectionMetadata> {
+        self.metadata.get(collection)
     }
 
-    pub fn get_widget(&self) -> Table {
+    /// Let items be marked, their id column then shows whether they are
+    pub fn enable_marks(&mut self) {
+        self.marked = Some(HashSet::new());
+    }
+
+    /// Mark the selected item, or unmark it if it is marked
+    pub fn toggle_mark(&mut self) {
+        if let (Some(marked), Some(ind)) = (self.marked.as_mut(), self.state.selected()) {
+            let index = self.items[ind].get_index();
+            if !marked.remove(&index) {
+                marked.insert(index);
+            }
+        }
+    }
+
+    /// Returns the indexes of the marked items, including those filtered out
+    pub fn get_marked(&self) -> HashSet<usize> {
+        self.marked.to_owned().unwrap_or_default()
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.to_owned();
+    }
+
+    pub fn get_sort_key(&self) -> Option<SortKey> {
+        self.sort_key
+    }
+
+    pub fn set_sort_key(&mut self, sort_key: Option<SortKey>) {
+        self.sort_key = sort_key;
+        self.sort_items();
+    }
+
+    fn sort_items(&mut self) {
+        self.items = self.unsorted_items.to_owned();
+        // Both sorts are stable, so priority still ranks items the sort key
+        // considers equal
+        let metadata = &self.metadata;
+        self.items.sort_by_key(|item| std::cmp::Reverse(
+            metadata.get(item.get_bookmark().get_collection()).map_or(0, |metadata| metadata.get_priority())
+        ));
+        if let Some(sort_key) = self.sort_key {
+            sort_results(&mut self.items, sort_key);
+        }
+    }
+
+    pub fn get_widget(&self) -> Table<'_> {
+        let colors = self.config.get_colors();
         // Define selected style for table row
-        let selected_row_style: Style = Style::default().fg(Color::LightYellow).add_modifier(Modifier::BOLD);
+        let selected_row_style: Style = Style::default().fg(colors.get_selected()).add_modifier(Modifier::BOLD);
 
         // Set table
         let header: Row = Row::new(
-            ["Command", "Annotation", "Tags", "Collection"]
+            ["Id", "Command", "Annotation", "Tags", "Collection"]
         ).style(
-            Style::default().fg(Color::LightGreen)
+            Style::default().fg(colors.get_header())
                             .add_modifier(Modifier::BOLD)
         );
-        let body_rows = self.items.iter().map(
-            |i| Row::new(
-                i.get_bookmark().to_vec().into_iter()
-            ).style(
-                Style::default().fg(Color::White)
+        let body_rows = self.items.iter().map(|i| {
+            let bookmark = i.get_bookmark();
+            let id = match &self.marked {
+                Some(marked) if marked.contains(&i.get_index()) => "[x]".to_owned(),
+                Some(_) => "[ ]".to_owned(),
+                None => bookmark.get_id().to_owned()
+            };
+            let mut cells: Vec<Cell> = std::iter::once(id)
+                .chain(bookmark.to_vec())
+                .map(Cell::from)
+                .collect();
+            if let Some(metadata) = self.metadata.get(bookmark.get_collection()) {
+                let mut cell = Cell::from(metadata.get_label(bookmark.get_collection()));
+                if let Some(color) = metadata.get_color().and_then(parse_color) {
+                    cell = cell.style(Style::default().fg(color));
+                }
+                cells[4] = cell;
+            }
+            Row::new(cells).style(
+                Style::default().fg(colors.get_text())
             )
-        );
+        });
         let t = Table::new(body_rows)
-            .block(Block::default().borders(Borders::ALL).title("Results"))
+            .block(Block::default().borders(Borders::ALL).title(
+                match self.sort_key {
+                    Some(sort_key) => format!("{} (sorted by {})", self.title, sort_key),
+                    None => self.title.to_owned()
+                }
+            ))
             .highlight_style(selected_row_style)
             .header(header)
             .column_spacing(1)
-            .widths(&[
-                Constraint::Percentage(35),
-                Constraint::Percentage(25),
-                Constraint::Percentage(20),
-                Constraint::Percentage(20),
-            ]);
+            .widths(&self.widths);
         t
     }
 
@@ -99,6 +192,15 @@ impl ResultTable {
     pub fn get_item(&self, ind: usize) -> &SearchResult {
         &self.items[ind]
     }
+
+    /// Select the row holding the bookmark at index of collection
+    pub fn select_item(&mut self, collection: &str, index: usize) {
+        self.state.select(
+            self.items.iter().position(
+                |item| item.get_index() == index && item.get_bookmark().get_collection() == collection
+            )
+        );
+    }
 }
 
 fn hashset_to_vec(results: &HashSet<SearchResult>) -> Vec<SearchResult> {